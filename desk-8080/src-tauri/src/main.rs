@@ -16,19 +16,27 @@ use synth_8080::{
     default_modules,
     delay::Delay,
     echo::{self, Echo},
+    env_gen::EnvelopeGenerator,
     envelope::EnvelopeFilter,
+    filter::FilterModule,
+    follower::EnvelopeFollower,
+    formant::FormantFilter,
     lfo::Lfo,
     midi_osc::MidiOsc,
     mk_synth,
-    osc::OscType,
+    noise::NoiseModule,
+    osc::{spectrum, sub::SubWaveform, OscType, Spectrum, SyncMode},
     output::Output,
     overdrive::{self, OverDrive},
     reverb::{self, ReverbModule},
     start_logging,
-    vco::Vco,
+    vco::{Vco, MAX_FM_INDEX},
     AudioGen, Float,
 };
-use synth_8080_lib::{FilterType, ModuleType};
+use synth_8080_lib::{
+    mseg::Mseg, EnvCurve, EnvSegment, FilterMode, FilterSlope, FilterType, ModuleType,
+    RetriggerMode,
+};
 use tauri::{async_runtime::spawn, Emitter, Manager, State, Window};
 use tracing::*;
 
@@ -99,6 +107,51 @@ fn get_vco_env(synth: State<'_, Arc<Controller>>) -> FilterType {
     synth.modules.lock().unwrap().mco[0].oscs[0].1.filter_type
 }
 
+#[tauri::command]
+fn set_filter_mode(synth: State<'_, Arc<Controller>>, mode: FilterMode, slope: FilterSlope) {
+    synth.modules.lock().unwrap().mco[0].set_filter_mode(mode, slope);
+}
+
+#[tauri::command]
+fn set_retrigger_mode(synth: State<'_, Arc<Controller>>, mode: RetriggerMode) {
+    synth.modules.lock().unwrap().mco[0].set_retrigger_mode(mode);
+}
+
+/// returns the MSEG breakpoints of the MCO in the text format of `synth_8080_lib::mseg`.
+#[tauri::command]
+fn get_mseg(synth: State<'_, Arc<Controller>>) -> String {
+    synth.modules.lock().unwrap().mco[0].oscs[0]
+        .1
+        .mseg
+        .to_string()
+}
+
+/// returns `false` if `definition` couldn't be parsed.
+#[tauri::command]
+fn set_mseg(synth: State<'_, Arc<Controller>>, definition: String) -> bool {
+    match definition.parse::<Mseg>() {
+        Ok(mseg) => {
+            synth.set_mseg(mseg);
+            true
+        }
+        Err(e) => {
+            error!("{e}");
+            false
+        }
+    }
+}
+
+/// returns `false` if the file couldn't be loaded.
+#[tauri::command]
+fn load_mseg(synth: State<'_, Arc<Controller>>, path: String) -> bool {
+    if let Err(e) = synth.load_mseg(&path) {
+        error!("failed to load MSEG {path}: {e}");
+        false
+    } else {
+        true
+    }
+}
+
 #[tauri::command]
 fn get_vco_osc(synth: State<'_, Arc<Controller>>) -> OscType {
     synth.modules.lock().unwrap().mco[0].oscs[0].0.osc.waveform
@@ -124,6 +177,17 @@ fn set_env_sustain(synth: State<'_, Arc<Controller>>, value: Float) {
     synth.modules.lock().unwrap().mco[0].set_sustain(value);
 }
 
+#[tauri::command]
+fn set_env_release(synth: State<'_, Arc<Controller>>, value: Float) {
+    synth.modules.lock().unwrap().mco[0].set_release(value);
+}
+
+/// sets the curve of one segment of the MCO envelope.
+#[tauri::command]
+fn set_env_curve(synth: State<'_, Arc<Controller>>, segment: EnvSegment, curve: EnvCurve) {
+    synth.modules.lock().unwrap().mco[0].set_curve(segment, curve);
+}
+
 #[tauri::command]
 fn set_env_decay_2(synth: State<'_, Arc<Controller>>, value: Float) {
     synth.modules.lock().unwrap().mco[0].set_decay_2(value);
@@ -370,21 +434,90 @@ fn update_connection_list(window: Window, synth: State<'_, Arc<Controller>>) {
     info!("started update checking thread");
 }
 
+/// returns the names of the MIDI ports and whether they are connected.
 #[tauri::command]
-fn list_midi_controllers(midi_con: State<'_, Arc<Mutex<Option<MIDIControls>>>>) -> Vec<String> {
+fn list_midi_controllers(
+    midi_con: State<'_, Arc<Mutex<Option<MIDIControls>>>>,
+) -> Vec<(String, bool)> {
     let mut connection = midi_con.lock().unwrap();
 
     if let Some(ref mut midi) = connection.deref_mut() {
+        let connected = midi.connected_ports();
+
         midi.list_ports()
             .unwrap_or(Vec::new())
             .into_iter()
-            .map(|port| port.1)
+            .map(|(_port, name)| {
+                let is_connected = connected.contains(&name);
+                (name, is_connected)
+            })
             .collect()
     } else {
         Vec::new()
     }
 }
 
+#[tauri::command]
+fn connect_midi(midi_con: State<'_, Arc<Mutex<Option<MIDIControls>>>>, name: String) {
+    let mut connection = midi_con.lock().unwrap();
+
+    if let Some(ref mut midi) = connection.deref_mut() {
+        if let Err(e) = midi.connect_by_name(&name) {
+            error!("failed to connect to midi device: {e}");
+        } else {
+            info!("connected to midi device {name}.");
+        }
+    }
+}
+
+#[tauri::command]
+fn disconnect_midi(midi_con: State<'_, Arc<Mutex<Option<MIDIControls>>>>, name: String) {
+    let mut connection = midi_con.lock().unwrap();
+
+    if let Some(ref mut midi) = connection.deref_mut() {
+        if let Err(e) = midi.disconnect(&name) {
+            error!("failed to disconnect from midi device: {e}");
+        } else {
+            info!("disconnected from midi device {name}.");
+        }
+    }
+}
+
+/// returns the name of the synth's virtual MIDI input port if it is open.
+#[tauri::command]
+fn get_virtual_midi_port(midi_con: State<'_, Arc<Mutex<Option<MIDIControls>>>>) -> Option<String> {
+    midi_con
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(|midi| midi.virtual_port())
+}
+
+/// renames the synth's virtual MIDI input port, an empty name closes it.
+#[tauri::command]
+fn set_virtual_midi_port(midi_con: State<'_, Arc<Mutex<Option<MIDIControls>>>>, name: String) {
+    let mut connection = midi_con.lock().unwrap();
+
+    if let Some(ref mut midi) = connection.deref_mut() {
+        if let Err(e) = midi
+            .set_virtual_port_name(Some(name))
+            .and_then(|_| midi.open_virtual_port())
+        {
+            error!("failed to open virtual midi port: {e}");
+        }
+    }
+}
+
+#[tauri::command]
+fn get_mpe(synth: State<'_, Arc<Controller>>) -> bool {
+    synth.modules.lock().unwrap().mco[0].mpe.enabled()
+}
+
+#[tauri::command]
+fn set_mpe(synth: State<'_, Arc<Controller>>, enabled: bool) {
+    synth.modules.lock().unwrap().mco[0].set_mpe(enabled);
+}
+
 #[tauri::command]
 fn reconnect_midi(midi_con: State<'_, Arc<Mutex<Option<MIDIControls>>>>) {
     let mut connection = midi_con.lock().unwrap();
@@ -430,6 +563,21 @@ fn get_module_outputs(module_type: ModuleType) -> Vec<String> {
         ModuleType::MCO => MidiOsc::get_output_names()
             .map(|name| format!("{name}"))
             .collect(),
+        ModuleType::Noise => NoiseModule::get_output_names()
+            .map(|name| format!("{name}"))
+            .collect(),
+        ModuleType::Envelope => EnvelopeGenerator::get_output_names()
+            .map(|name| format!("{name}"))
+            .collect(),
+        ModuleType::Filter => FilterModule::get_output_names()
+            .map(|name| format!("{name}"))
+            .collect(),
+        ModuleType::Formant => FormantFilter::get_output_names()
+            .map(|name| format!("{name}"))
+            .collect(),
+        ModuleType::Follower => EnvelopeFollower::get_output_names()
+            .map(|name| format!("{name}"))
+            .collect(),
         ModuleType::Echo => Echo::get_output_names()
             .map(|name| format!("{name}"))
             .collect(),
@@ -466,6 +614,21 @@ fn get_module_inputs(module_type: ModuleType) -> Vec<String> {
         ModuleType::MCO => MidiOsc::get_input_names()
             .map(|name| format!("{name}"))
             .collect(),
+        ModuleType::Noise => NoiseModule::get_input_names()
+            .map(|name| format!("{name}"))
+            .collect(),
+        ModuleType::Envelope => EnvelopeGenerator::get_input_names()
+            .map(|name| format!("{name}"))
+            .collect(),
+        ModuleType::Filter => FilterModule::get_input_names()
+            .map(|name| format!("{name}"))
+            .collect(),
+        ModuleType::Formant => FormantFilter::get_input_names()
+            .map(|name| format!("{name}"))
+            .collect(),
+        ModuleType::Follower => EnvelopeFollower::get_input_names()
+            .map(|name| format!("{name}"))
+            .collect(),
         ModuleType::Echo => Echo::get_input_names()
             .map(|name| format!("{name}"))
             .collect(),
@@ -494,14 +657,275 @@ fn set_env_cutoff(synth: State<'_, Arc<Controller>>, value: Float) {
     synth.modules.lock().unwrap().mco[0].set_cutoff(value);
 }
 
+/// `value` is between 0 and 1.0, 1.0 (the default) scales the cutoff by the envelope, 0.5 leaves
+/// it alone and below that inverts the envelope.
+#[tauri::command]
+fn set_filter_env_amount(synth: State<'_, Arc<Controller>>, value: Float) {
+    synth.modules.lock().unwrap().mco[0].set_filter_env_amount(value * 2.0 - 1.0);
+}
+
+#[tauri::command]
+fn set_filter_env_atk(synth: State<'_, Arc<Controller>>, value: Float) {
+    synth.modules.lock().unwrap().mco[0].set_filter_attack(value);
+}
+
+#[tauri::command]
+fn set_filter_env_decay(synth: State<'_, Arc<Controller>>, value: Float) {
+    synth.modules.lock().unwrap().mco[0].set_filter_decay(value);
+}
+
+#[tauri::command]
+fn set_filter_env_sustain(synth: State<'_, Arc<Controller>>, value: Float) {
+    synth.modules.lock().unwrap().mco[0].set_filter_sustain(value);
+}
+
+#[tauri::command]
+fn set_filter_env_release(synth: State<'_, Arc<Controller>>, value: Float) {
+    synth.modules.lock().unwrap().mco[0].set_filter_release(value);
+}
+
+#[tauri::command]
+fn set_key_tracking(synth: State<'_, Arc<Controller>>, value: Float) {
+    synth.modules.lock().unwrap().mco[0].set_key_tracking(value);
+}
+
+#[tauri::command]
+fn set_velocity_amount(synth: State<'_, Arc<Controller>>, value: Float) {
+    synth.modules.lock().unwrap().mco[0].set_velocity_amount(value);
+}
+
 #[tauri::command]
 fn set_env_resonance(synth: State<'_, Arc<Controller>>, value: Float) {
     synth.modules.lock().unwrap().mco[0].set_resonance(value);
 }
 
+#[tauri::command]
+fn load_midi_file(synth: State<'_, Arc<Controller>>, path: String) -> Result<Float, String> {
+    synth.load_midi_file(&path).map_err(|e| {
+        error!("failed to load midi file {path}: {e}");
+        e.to_string()
+    })?;
+
+    Ok(synth.player.lock().unwrap().length())
+}
+
+#[tauri::command]
+fn play_midi_file(synth: State<'_, Arc<Controller>>) {
+    synth.play_midi_file();
+}
+
+#[tauri::command]
+fn stop_midi_file(synth: State<'_, Arc<Controller>>) {
+    synth.stop_midi_file();
+}
+
+#[tauri::command]
+fn set_midi_file_loop(synth: State<'_, Arc<Controller>>, looping: bool) {
+    synth.set_midi_file_loop(looping);
+}
+
+#[tauri::command]
+fn seek_midi_file(synth: State<'_, Arc<Controller>>, seconds: Float) {
+    synth.seek_midi_file(seconds);
+}
+
+#[tauri::command]
+fn start_recording(synth: State<'_, Arc<Controller>>) {
+    synth.start_recording();
+}
+
+#[tauri::command]
+fn stop_recording(synth: State<'_, Arc<Controller>>, path: String) -> Result<usize, String> {
+    synth.stop_recording(&path).map_err(|e| {
+        error!("failed to save midi recording to {path}: {e}");
+        e.to_string()
+    })
+}
+
+#[tauri::command]
+fn set_reference_pitch(synth: State<'_, Arc<Controller>>, freq: Float) {
+    synth.set_reference_pitch(freq);
+}
+
+/// returns `false` if the file couldn't be loaded.
+#[tauri::command]
+fn load_scale(synth: State<'_, Arc<Controller>>, path: String) -> bool {
+    if let Err(e) = synth.load_scale(&path) {
+        error!("failed to load scale {path}: {e}");
+        false
+    } else {
+        true
+    }
+}
+
+/// returns `false` if the file couldn't be loaded.
+#[tauri::command]
+fn load_keyboard_map(synth: State<'_, Arc<Controller>>, path: String) -> bool {
+    if let Err(e) = synth.load_keyboard_map(&path) {
+        error!("failed to load keyboard map {path}: {e}");
+        false
+    } else {
+        true
+    }
+}
+
+#[tauri::command]
+fn reset_tuning(synth: State<'_, Arc<Controller>>) {
+    synth.reset_tuning();
+}
+
+/// returns `false` if the file couldn't be loaded.
+#[tauri::command]
+fn load_wavetable(synth: State<'_, Arc<Controller>>, path: String) -> bool {
+    if let Err(e) = synth.load_wavetable(&path) {
+        error!("failed to load wavetable {path}: {e}");
+        false
+    } else {
+        true
+    }
+}
+
+/// returns `false` if the file couldn't be loaded.
+#[tauri::command]
+fn load_lfo_wavetable(synth: State<'_, Arc<Controller>>, id: u8, path: String) -> bool {
+    if let Err(e) = synth.load_lfo_wavetable(id as usize, &path) {
+        error!("failed to load wavetable {path}: {e}");
+        false
+    } else {
+        true
+    }
+}
+
+#[tauri::command]
+fn set_wavetable_position(synth: State<'_, Arc<Controller>>, value: Float) {
+    synth.modules.lock().unwrap().mco[0].set_wavetable_position(value);
+}
+
+#[tauri::command]
+fn set_pulse_width(synth: State<'_, Arc<Controller>>, value: Float) {
+    synth.modules.lock().unwrap().mco[0].set_pulse_width(value);
+}
+
+/// `value` is between 0 and 1.0.
+#[tauri::command]
+fn set_fm_index(synth: State<'_, Arc<Controller>>, value: Float) {
+    synth.modules.lock().unwrap().mco[0].set_fm_index(value * MAX_FM_INDEX);
+}
+
+#[tauri::command]
+fn set_through_zero(synth: State<'_, Arc<Controller>>, enabled: bool) {
+    synth.modules.lock().unwrap().mco[0].set_through_zero(enabled);
+}
+
+#[tauri::command]
+fn set_unison(synth: State<'_, Arc<Controller>>, n: usize) {
+    synth.modules.lock().unwrap().mco[0].set_unison(n);
+}
+
+#[tauri::command]
+fn set_detune(synth: State<'_, Arc<Controller>>, value: Float) {
+    synth.modules.lock().unwrap().mco[0].set_detune(value);
+}
+
+#[tauri::command]
+fn set_spread(synth: State<'_, Arc<Controller>>, value: Float) {
+    synth.modules.lock().unwrap().mco[0].set_spread(value);
+}
+
+#[tauri::command]
+fn set_sub_level(synth: State<'_, Arc<Controller>>, value: Float) {
+    synth.modules.lock().unwrap().mco[0].set_sub_level(value);
+}
+
+#[tauri::command]
+fn set_sub_octaves(synth: State<'_, Arc<Controller>>, octaves: u8) {
+    synth.modules.lock().unwrap().mco[0].set_sub_octaves(octaves);
+}
+
+#[tauri::command]
+fn set_sub_waveform(synth: State<'_, Arc<Controller>>, waveform: SubWaveform) {
+    synth.modules.lock().unwrap().mco[0].set_sub_waveform(waveform);
+}
+
+/// returns the amplitude of every harmonic played in overtone mode.
+#[tauri::command]
+fn get_harmonics(synth: State<'_, Arc<Controller>>) -> Vec<Float> {
+    synth.modules.lock().unwrap().mco[0]
+        .harmonics()
+        .amplitudes
+        .to_vec()
+}
+
+/// the phases are left as they are when `phases` is missing.
+#[tauri::command]
+fn set_harmonics(
+    synth: State<'_, Arc<Controller>>,
+    amplitudes: Vec<Float>,
+    phases: Option<Vec<Float>>,
+) {
+    let mut mods = synth.modules.lock().unwrap();
+    let phases = phases.unwrap_or_else(|| mods.mco[0].harmonics().phases.to_vec());
+
+    mods.mco[0].set_harmonics(Spectrum::new(&amplitudes, &phases));
+}
+
+#[tauri::command]
+fn get_harmonic_presets() -> Vec<String> {
+    spectrum::PRESETS
+        .iter()
+        .map(|name| name.to_string())
+        .collect()
+}
+
+/// returns the new amplitudes, or `None` if there is no such preset.
+#[tauri::command]
+fn set_harmonic_preset(synth: State<'_, Arc<Controller>>, name: String) -> Option<Vec<Float>> {
+    let spectrum = Spectrum::preset(&name)?;
+    let amplitudes = spectrum.amplitudes.to_vec();
+    synth.modules.lock().unwrap().mco[0].set_harmonics(spectrum);
+
+    Some(amplitudes)
+}
+
+/// returns the new amplitudes, or `None` if the registration is invalid.
+#[tauri::command]
+fn set_drawbars(synth: State<'_, Arc<Controller>>, registration: String) -> Option<Vec<Float>> {
+    let spectrum = match Spectrum::drawbars(&registration) {
+        Ok(spectrum) => spectrum,
+        Err(e) => {
+            error!("invalid drawbar registration {registration}: {e}");
+            return None;
+        }
+    };
+    let amplitudes = spectrum.amplitudes.to_vec();
+    synth.modules.lock().unwrap().mco[0].set_harmonics(spectrum);
+
+    Some(amplitudes)
+}
+
+#[tauri::command]
+fn set_sync_mode(synth: State<'_, Arc<Controller>>, mode: SyncMode) {
+    synth.modules.lock().unwrap().mco[0].set_sync_mode(mode);
+}
+
+#[tauri::command]
+fn set_lfo_wavetable_position(synth: State<'_, Arc<Controller>>, id: u8, value: Float) {
+    synth.modules.lock().unwrap().lfo[id as usize].set_position(value);
+}
+
 fn start_midi(synth: Arc<Controller>) -> anyhow::Result<MIDIControls> {
     let mut midi_con = MIDIControls::new(synth)?;
-    midi_con.connect_default()?;
+
+    if let Err(e) = midi_con.open_virtual_port() {
+        warn!("{e}");
+    }
+
+    // devices in `SYNTH_8080_MIDI_DEVICES` get picked up by the watcher once plugged in.
+    if let Err(e) = midi_con.connect_default() {
+        warn!("no midi device connected: {e}");
+    }
+
+    midi_con.start_watcher();
 
     Ok(midi_con)
 }
@@ -598,6 +1022,20 @@ async fn main() {
             set_env_break,
             set_env_sustain,
             set_env_decay_2,
+            set_env_release,
+            set_env_curve,
+            set_retrigger_mode,
+            set_filter_mode,
+            set_filter_env_amount,
+            set_filter_env_atk,
+            set_filter_env_decay,
+            set_filter_env_sustain,
+            set_filter_env_release,
+            set_key_tracking,
+            set_velocity_amount,
+            get_mseg,
+            set_mseg,
+            load_mseg,
             set_echo_vol,
             set_echo_speed,
             set_chorus_vol,
@@ -611,7 +1049,13 @@ async fn main() {
             disconnect,
             update_connection_list,
             list_midi_controllers,
+            connect_midi,
+            disconnect_midi,
             reconnect_midi,
+            get_virtual_midi_port,
+            set_virtual_midi_port,
+            get_mpe,
+            set_mpe,
             set_polyphony,
             // get_connection_graph,
             get_module_inputs,
@@ -619,6 +1063,36 @@ async fn main() {
             enable_overtones,
             set_env_cutoff,
             set_env_resonance,
+            load_midi_file,
+            play_midi_file,
+            stop_midi_file,
+            set_midi_file_loop,
+            seek_midi_file,
+            start_recording,
+            stop_recording,
+            set_reference_pitch,
+            load_scale,
+            load_keyboard_map,
+            reset_tuning,
+            load_wavetable,
+            load_lfo_wavetable,
+            set_wavetable_position,
+            set_lfo_wavetable_position,
+            set_pulse_width,
+            set_fm_index,
+            set_through_zero,
+            set_sync_mode,
+            set_unison,
+            set_detune,
+            set_spread,
+            set_sub_level,
+            set_sub_octaves,
+            set_sub_waveform,
+            get_harmonics,
+            set_harmonics,
+            get_harmonic_presets,
+            set_harmonic_preset,
+            set_drawbars,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
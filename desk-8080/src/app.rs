@@ -3,13 +3,20 @@ use serde::{Deserialize, Serialize};
 use serde_wasm_bindgen::{from_value, to_value};
 use std::str::FromStr;
 use strum::IntoEnumIterator;
-use synth_8080_lib::{FilterType, ModuleType, OscType};
+use synth_8080_lib::{
+    EnvCurve, EnvSegment, FilterMode, FilterSlope, FilterType, ModuleType, OscType, RetriggerMode,
+    SubWaveform, SyncMode,
+};
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
 extern "C" {
     #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "core"])]
     async fn invoke(cmd: &str, args: JsValue) -> JsValue;
+
+    /// like `invoke`, but returns the error when the command fails.
+    #[wasm_bindgen(catch, js_namespace = ["window", "__TAURI__", "core"], js_name = invoke)]
+    async fn try_invoke(cmd: &str, args: JsValue) -> Result<JsValue, JsValue>;
 }
 
 pub const SLIDER_MAX: usize = 100_000;
@@ -61,10 +68,27 @@ struct EnvSetArgs {
     value: SliderVal,
 }
 
-// #[derive(Serialize, Deserialize)]
-// struct VcoSetEnvType {
-//     env_type: FilterType,
-// }
+#[derive(Serialize, Deserialize)]
+struct VcoSetEnvType {
+    env_type: FilterType,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FilterModeArgs {
+    mode: FilterMode,
+    slope: FilterSlope,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RetriggerModeArgs {
+    mode: RetriggerMode,
+}
+
+#[derive(Serialize, Deserialize)]
+struct EnvCurveArgs {
+    segment: EnvSegment,
+    curve: EnvCurve,
+}
 
 #[derive(Serialize, Deserialize)]
 struct EditConnectionArgs {
@@ -82,6 +106,88 @@ struct SetOvertonesArgs {
     enabled: bool,
 }
 
+#[derive(Serialize, Deserialize)]
+struct MpeArgs {
+    enabled: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ThroughZeroArgs {
+    enabled: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SyncModeArgs {
+    mode: SyncMode,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SubOctavesArgs {
+    octaves: u8,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SubWaveformArgs {
+    waveform: SubWaveform,
+}
+
+#[derive(Serialize, Deserialize)]
+struct HarmonicsArgs {
+    amplitudes: Vec<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct HarmonicPresetArgs {
+    name: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DrawbarArgs {
+    registration: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MidiDeviceArgs {
+    name: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MidiFileArgs {
+    path: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MsegArgs {
+    definition: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MidiFileLoopArgs {
+    looping: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MidiFileSeekArgs {
+    seconds: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ReferencePitchArgs {
+    freq: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LfoWavetableArgs {
+    id: u8,
+    path: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LfoPositionArgs {
+    id: u8,
+    value: f32,
+}
+
 /// converts a slider position to a float that represents where the slider is on its "throw". will
 /// return a float between 0 and 1. returns an f32 for consistnacy and "better safe then sorry"
 /// reasons between "f64" samples and "f32" samples modes.
@@ -116,7 +222,10 @@ pub fn App() -> impl IntoView {
                                     <Overdrive/>
                                 </div>
                             </div>
-                            <EnvFilter/>
+                            <div>
+                                <EnvFilter/>
+                                <FilterEnv/>
+                            </div>
                         </div>
                     <div class="col-span-2 grid grid-cols-3 gap-4">
                         <Echo/>
@@ -127,20 +236,26 @@ pub fn App() -> impl IntoView {
                     </div>
                 </div>
             </div>
+            <Harmonics/>
             <Connections/>
         </main>
     }
 }
 
 #[component]
-fn Slider(mut on_input: Box<dyn FnMut(f32)>) -> impl IntoView {
+fn Slider(
+    mut on_input: Box<dyn FnMut(f32)>,
+    /// where the slider starts, the middle unless the back end defaults to something else.
+    #[prop(default = SLIDER_MAX / 2)]
+    start: usize,
+) -> impl IntoView {
     // TODO: make it change with back end.
     view! {
         <input
             type="range"
             min=0
             max=SLIDER_MAX
-            value=SLIDER_MAX / 2
+            value=start
             on:input=move |ev| {
                 let position: usize = event_target_value(&ev).parse().unwrap();
                 on_input(slider_to_float(position))
@@ -149,6 +264,89 @@ fn Slider(mut on_input: Box<dyn FnMut(f32)>) -> impl IntoView {
     }
 }
 
+#[component]
+fn Harmonics() -> impl IntoView {
+    let (amplitudes, set_amplitudes) = signal(Vec::<f32>::new());
+    let (presets, set_presets) = signal(Vec::<String>::new());
+
+    spawn_local(async move {
+        set_amplitudes.set(
+            from_value(invoke("get_harmonics", to_value(&Empty {}).unwrap()).await)
+                .unwrap_or_default(),
+        );
+        set_presets.set(
+            from_value(invoke("get_harmonic_presets", to_value(&Empty {}).unwrap()).await)
+                .unwrap_or_default(),
+        );
+    });
+
+    view! {
+        <div class="text-center">
+            <h1> Harmonics </h1>
+            <div class="border-4 rounded-md border-black text-center p-4">
+                <div class="flex flex-row justify-center gap-1">
+                    { move ||
+                        amplitudes.get().into_iter().enumerate().map(|(i, amp)| {
+                            view! {
+                                <div class="flex flex-col items-center">
+                                    <input type="range" class="[writing-mode:vertical-lr] rotate-180 h-32"
+                                        min=0
+                                        max=SLIDER_MAX
+                                        prop:value=(amp * SLIDER_MAX as f32) as usize
+                                        on:change=move |ev| {
+                                            let position: usize = event_target_value(&ev).parse().unwrap_or(0);
+                                            let mut amps = amplitudes.get_untracked();
+                                            amps[i] = position as f32 / SLIDER_MAX as f32;
+                                            set_amplitudes.set(amps.clone());
+
+                                            spawn_local(async move {
+                                                invoke("set_harmonics", to_value(&HarmonicsArgs { amplitudes: amps }).unwrap()).await;
+                                            })
+                                        }
+                                    />
+                                    <p> { i + 1 } </p>
+                                </div>
+                            }
+                        }).collect::<Vec<_>>()
+                    }
+                </div>
+                <select
+                    on:change=move |ev| {
+                        let name = event_target_value(&ev);
+
+                        spawn_local(async move {
+                            if let Ok(Some(amps)) = from_value::<Option<Vec<f32>>>(
+                                invoke("set_harmonic_preset", to_value(&HarmonicPresetArgs { name }).unwrap()).await,
+                            ) {
+                                set_amplitudes.set(amps);
+                            }
+                        })
+                    }
+                >
+                    { move ||
+                        presets.get().into_iter().map(|name| {
+                            view! { <option value=name.clone()> { name } </option> }
+                        }).collect::<Vec<_>>()
+                    }
+                </select>
+                <input type="text" placeholder="drawbars, eg. 888000000"
+                    on:change=move |ev| {
+                        let registration = event_target_value(&ev);
+
+                        spawn_local(async move {
+                            if let Ok(Some(amps)) = from_value::<Option<Vec<f32>>>(
+                                invoke("set_drawbars", to_value(&DrawbarArgs { registration }).unwrap()).await,
+                            ) {
+                                set_amplitudes.set(amps);
+                            }
+                        })
+                    }
+                />
+            </div>
+        </div>
+    }
+}
+
 #[component]
 fn LFO(index: u8) -> impl IntoView {
     // make signal for LFO state
@@ -198,6 +396,20 @@ fn LFO(index: u8) -> impl IntoView {
         })
     };
 
+    let on_position_input = move |val| {
+        spawn_local(async move {
+            invoke(
+                "set_lfo_wavetable_position",
+                to_value(&LfoPositionArgs {
+                    id: index,
+                    value: val,
+                })
+                .unwrap(),
+            )
+            .await;
+        })
+    };
+
     view! {
         <div class="border-4 rounded-md border-black text-center">
             <h1>{move || format!("LFO {}", index) }</h1>
@@ -211,6 +423,23 @@ fn LFO(index: u8) -> impl IntoView {
                     // volume slider
                     <p>"vol."</p>
                     <Slider on_input=Box::new(on_vol_input)/>
+
+                    // wavetable position slider
+                    <p>"position"</p>
+                    <Slider on_input=Box::new(on_position_input)/>
+                    <input type="text" placeholder="wavetable.wav"
+                        on:change=move |ev| {
+                            let path = event_target_value(&ev);
+
+                            spawn_local(async move {
+                                invoke(
+                                    "load_lfo_wavetable",
+                                    to_value(&LfoWavetableArgs { id: index, path }).unwrap(),
+                                )
+                                .await;
+                            })
+                        }
+                    />
                 </div>
 
                 // oscillator type indicator
@@ -303,6 +532,113 @@ fn VCO() -> impl IntoView {
         })
     };
 
+    let on_position_input = move |pos| {
+        spawn_local(async move {
+            invoke(
+                "set_wavetable_position",
+                to_value(&EnvSetArgs { value: pos }).unwrap(),
+            )
+            .await;
+        })
+    };
+
+    let on_pulse_width_input = move |width| {
+        spawn_local(async move {
+            invoke(
+                "set_pulse_width",
+                to_value(&EnvSetArgs { value: width }).unwrap(),
+            )
+            .await;
+        })
+    };
+
+    let on_fm_index_input = move |index| {
+        spawn_local(async move {
+            invoke(
+                "set_fm_index",
+                to_value(&EnvSetArgs { value: index }).unwrap(),
+            )
+            .await;
+        })
+    };
+
+    let (through_zero, set_through_zero) = signal(false);
+
+    let on_through_zero_click = move |_ev| {
+        let enabled = !through_zero.get_untracked();
+        set_through_zero.set(enabled);
+
+        spawn_local(async move {
+            invoke(
+                "set_through_zero",
+                to_value(&ThroughZeroArgs { enabled }).unwrap(),
+            )
+            .await;
+        })
+    };
+
+    let (sync_mode, set_sync_mode) = signal(SyncMode::Hard);
+
+    let on_sync_mode_click = move |_ev| {
+        let mode = match sync_mode.get_untracked() {
+            SyncMode::Hard => SyncMode::Soft,
+            SyncMode::Soft => SyncMode::Hard,
+        };
+        set_sync_mode.set(mode);
+
+        spawn_local(async move {
+            invoke("set_sync_mode", to_value(&SyncModeArgs { mode }).unwrap()).await;
+        })
+    };
+
+    let on_detune_input = move |detune| {
+        spawn_local(async move {
+            invoke(
+                "set_detune",
+                to_value(&EnvSetArgs { value: detune }).unwrap(),
+            )
+            .await;
+        })
+    };
+
+    let on_spread_input = move |spread| {
+        spawn_local(async move {
+            invoke(
+                "set_spread",
+                to_value(&EnvSetArgs { value: spread }).unwrap(),
+            )
+            .await;
+        })
+    };
+
+    let on_sub_level_input = move |level| {
+        spawn_local(async move {
+            invoke(
+                "set_sub_level",
+                to_value(&EnvSetArgs { value: level }).unwrap(),
+            )
+            .await;
+        })
+    };
+
+    let (sub_waveform, set_sub_waveform) = signal(SubWaveform::Square);
+
+    let on_sub_waveform_click = move |_ev| {
+        let waveform = match sub_waveform.get_untracked() {
+            SubWaveform::Square => SubWaveform::Sine,
+            SubWaveform::Sine => SubWaveform::Square,
+        };
+        set_sub_waveform.set(waveform);
+
+        spawn_local(async move {
+            invoke(
+                "set_sub_waveform",
+                to_value(&SubWaveformArgs { waveform }).unwrap(),
+            )
+            .await;
+        })
+    };
+
     let on_overtones_click = move |_ev| {
         set_overtones.set(!overtones.get());
 
@@ -328,6 +664,77 @@ fn VCO() -> impl IntoView {
                         <p> "vol." </p>
                         <Slider on_input=Box::new(on_volume_input)/>
                     </div>
+                    <div>
+                        <p> "pulse width" </p>
+                        <Slider on_input=Box::new(on_pulse_width_input)/>
+                    </div>
+                    <div>
+                        <p> "FM index" </p>
+                        <Slider on_input=Box::new(on_fm_index_input)/>
+                        <button on:click=on_through_zero_click>
+                            { move || if through_zero.get() { "[x] " } else { "[ ] " } }
+                            Through Zero
+                        </button>
+                        <button on:click=on_sync_mode_click>
+                            { move || format!("Sync: {:?}", sync_mode.get()) }
+                        </button>
+                    </div>
+                    <div>
+                        <p> "wavetable position" </p>
+                        <Slider on_input=Box::new(on_position_input)/>
+                        <input type="text" placeholder="wavetable.wav"
+                            on:change=move |ev| {
+                                let path = event_target_value(&ev);
+
+                                spawn_local(async move {
+                                    invoke("load_wavetable", to_value(&MidiFileArgs { path }).unwrap()).await;
+                                })
+                            }
+                        />
+                    </div>
+                    <div>
+                        "Unison: "
+                        <input type="number"
+                            on:change=move |ev| {
+                                spawn_local(async move {
+                                    invoke(
+                                        "set_unison",
+                                        to_value(&PolyphonySetArgs { n: event_target_value(&ev).parse().unwrap_or(1) }).unwrap(),
+                                    )
+                                    .await;
+                                })
+                            }
+                            min="1"
+                            max="8"
+                            value="1"
+                        />
+                        <p> "detune" </p>
+                        <Slider on_input=Box::new(on_detune_input)/>
+                        <p> "spread" </p>
+                        <Slider on_input=Box::new(on_spread_input)/>
+                    </div>
+                    <div>
+                        <p> "sub level" </p>
+                        <Slider on_input=Box::new(on_sub_level_input)/>
+                        "Sub octaves: "
+                        <input type="number"
+                            on:change=move |ev| {
+                                spawn_local(async move {
+                                    invoke(
+                                        "set_sub_octaves",
+                                        to_value(&SubOctavesArgs { octaves: event_target_value(&ev).parse().unwrap_or(1) }).unwrap(),
+                                    )
+                                    .await;
+                                })
+                            }
+                            min="1"
+                            max="2"
+                            value="1"
+                        />
+                        <button on:click=on_sub_waveform_click>
+                            { move || format!("Sub: {:?}", sub_waveform.get()) }
+                        </button>
+                    </div>
                     <div>
                         "Polyphony: "
                         <input type="number" id="src_mod_index" name="src_mod_index"
@@ -474,6 +881,218 @@ fn EnvFilter() -> impl IntoView {
         }
     };
 
+    let (filter_mode, set_filter_mode) = signal(FilterMode::Moog);
+    let (filter_slope, set_filter_slope) = signal(FilterSlope::Db12);
+
+    let send_filter_mode = move |mode: FilterMode, slope: FilterSlope| {
+        set_filter_mode.set(mode);
+        set_filter_slope.set(slope);
+
+        spawn_local(async move {
+            invoke(
+                "set_filter_mode",
+                to_value(&FilterModeArgs { mode, slope }).unwrap(),
+            )
+            .await;
+        })
+    };
+
+    let on_slope_click = move |_ev| {
+        let slope = match filter_slope.get_untracked() {
+            FilterSlope::Db12 => FilterSlope::Db24,
+            FilterSlope::Db24 => FilterSlope::Db12,
+        };
+
+        send_filter_mode(filter_mode.get_untracked(), slope)
+    };
+
+    let (retrigger_mode, set_retrigger_mode) = signal(RetriggerMode::Restart);
+
+    let on_retrigger_click = move |_ev| {
+        let mode = match retrigger_mode.get_untracked() {
+            RetriggerMode::Reset => RetriggerMode::Restart,
+            RetriggerMode::Restart => RetriggerMode::Legato,
+            RetriggerMode::Legato => RetriggerMode::Reset,
+        };
+        set_retrigger_mode.set(mode);
+
+        spawn_local(async move {
+            invoke(
+                "set_retrigger_mode",
+                to_value(&RetriggerModeArgs { mode }).unwrap(),
+            )
+            .await;
+        })
+    };
+
+    let release = move || {
+        let set_release = move |pos| {
+            spawn_local(async move {
+                invoke(
+                    "set_env_release",
+                    to_value(&EnvSetArgs { value: pos }).unwrap(),
+                )
+                .await;
+            })
+        };
+
+        if env_type.get() == FilterType::ADSR {
+            view! {
+                <p> "release" </p>
+                <Slider on_input=Box::new(set_release)/>
+            }
+            .into_any()
+        } else {
+            nothing().into_any()
+        }
+    };
+
+    let curves = move || {
+        // defaults match `envelope::adsr::Filter::new`
+        let curve_button = move |segment: EnvSegment, default: EnvCurve| {
+            let (curve, set_curve) = signal(default);
+
+            let on_click = move |_| {
+                let next = EnvCurve::iter()
+                    .cycle()
+                    .skip_while(|c| *c != curve.get())
+                    .nth(1)
+                    .unwrap_or_default();
+
+                spawn_local(async move {
+                    invoke(
+                        "set_env_curve",
+                        to_value(&EnvCurveArgs {
+                            segment,
+                            curve: next,
+                        })
+                        .unwrap(),
+                    )
+                    .await;
+                    set_curve.set(next);
+                })
+            };
+
+            view! {
+                <button on:click=on_click>
+                    { move || format!("{segment:?}: {:?}", curve.get()) }
+                </button>
+            }
+        };
+
+        if env_type.get() == FilterType::ADSR {
+            view! {
+                <div class="grid grid-flex-row text-left">
+                    { curve_button(EnvSegment::Attack, EnvCurve::Linear) }
+                    { curve_button(EnvSegment::Decay, EnvCurve::Exponential) }
+                    { curve_button(EnvSegment::Release, EnvCurve::Exponential) }
+                </div>
+            }
+            .into_any()
+        } else {
+            view! { <div> </div> }.into_any()
+        }
+    };
+
+    let mseg = move || {
+        let (definition, set_definition) = signal(String::new());
+        let (valid, set_valid) = signal(true);
+
+        spawn_local(async move {
+            set_definition.set(
+                invoke("get_mseg", to_value(&Empty {}).unwrap())
+                    .await
+                    .as_string()
+                    .unwrap_or_default(),
+            )
+        });
+
+        let on_change = move |ev| {
+            let definition = event_target_value(&ev);
+
+            spawn_local(async move {
+                let res = invoke("set_mseg", to_value(&MsegArgs { definition }).unwrap()).await;
+                set_valid.set(res.as_bool().unwrap_or(false));
+            })
+        };
+
+        let on_load = move |ev| {
+            let path = event_target_value(&ev);
+
+            spawn_local(async move {
+                let res = invoke("load_mseg", to_value(&MidiFileArgs { path }).unwrap()).await;
+                set_valid.set(res.as_bool().unwrap_or(false));
+
+                if valid.get_untracked() {
+                    set_definition.set(
+                        invoke("get_mseg", to_value(&Empty {}).unwrap())
+                            .await
+                            .as_string()
+                            .unwrap_or_default(),
+                    )
+                }
+            })
+        };
+
+        if env_type.get() == FilterType::MSEG {
+            view! {
+                <div>
+                    <p> "breakpoints" </p>
+                    <input type="text" prop:value=definition on:change=on_change/>
+                    <input type="text" placeholder="envelope.mseg" on:change=on_load/>
+                    <p> { move || if valid.get() { "" } else { "invalid MSEG" } } </p>
+                </div>
+            }
+            .into_any()
+        } else {
+            view! { <div> </div> }.into_any()
+        }
+    };
+
+    let env_break = move || {
+        let set_break = move |pos| {
+            spawn_local(async move {
+                invoke(
+                    "set_env_break",
+                    to_value(&EnvSetArgs { value: pos }).unwrap(),
+                )
+                .await;
+            })
+        };
+
+        if env_type.get() == FilterType::ADBDR {
+            view! {
+                <p> "break" </p>
+                <Slider on_input=Box::new(set_break)/>
+            }
+            .into_any()
+        } else {
+            nothing().into_any()
+        }
+    };
+
+    let decay_2 = move || {
+        let set_decay_2 = move |pos| {
+            spawn_local(async move {
+                invoke(
+                    "set_env_decay_2",
+                    to_value(&EnvSetArgs { value: pos }).unwrap(),
+                )
+                .await;
+            })
+        };
+
+        if env_type.get() == FilterType::ADBDR {
+            view! {
+                <p> "decay 2" </p>
+                <Slider on_input=Box::new(set_decay_2)/>
+            }
+            .into_any()
+        } else {
+            nothing().into_any()
+        }
+    };
+
     let cutoff = move || {
         let set_cutoff = move |pos| {
             let pos = if pos == SliderVal::MIN {
@@ -522,9 +1141,110 @@ fn EnvFilter() -> impl IntoView {
                     { attack }
                     { decay }
                     { sustain }
+                    { env_break }
+                    { decay_2 }
+                    { release }
                     { cutoff }
                     { resonance }
                 </div>
+
+                { curves }
+                // filter mode indicator
+                <div class="grid grid-flex-row text-left">
+                    <For
+                        each=move || FilterMode::iter()
+                        key=move |key| (key.clone(), *key == filter_mode.get())
+                        children=move |mode| {
+                            view! {
+                                <div>
+                                    <button on:click=move |_| {
+                                        send_filter_mode(mode, filter_slope.get_untracked())
+                                    }>
+                                        { move ||
+                                            if mode == filter_mode.get() {
+                                                format!("- [x] {mode:?}")
+                                            } else {
+                                                format!("- [ ] {mode:?}")
+                                            }
+                                        }
+                                    </button>
+                                </div>
+                            }
+                        }
+                    />
+                </div>
+                <button on:click=on_slope_click>
+                    { move || format!("Slope: {:?}", filter_slope.get()) }
+                </button>
+                <button on:click=on_retrigger_click>
+                    { move || format!("Retrigger: {:?}", retrigger_mode.get()) }
+                </button>
+                { mseg }
+
+                // envelope type indicator
+                <div class="grid grid-flex-row text-left">
+                    <For
+                        each=move || FilterType::iter()
+                        key=move |key| (key.clone(), *key == env_type.get())
+                        children=move |env| {
+                            view! {
+                                <div>
+                                    <button on:click=move |_| {
+                                        spawn_local(async move {
+                                            invoke(
+                                                "set_env",
+                                                to_value(&VcoSetEnvType { env_type: env }).unwrap(),
+                                            )
+                                            .await;
+                                            set_env_type.set(env);
+                                        })
+                                    }>
+                                        { move ||
+                                            if env == env_type.get() {
+                                                format!("- [x] {env:?}")
+                                            } else {
+                                                format!("- [ ] {env:?}")
+                                            }
+                                        }
+                                    </button>
+                                </div>
+                            }
+                        }
+                    />
+                </div>
+            </div>
+        </div>
+    }
+}
+
+#[component]
+fn FilterEnv() -> impl IntoView {
+    let slider = move |cmd: &'static str| {
+        move |pos| {
+            spawn_local(async move {
+                invoke(cmd, to_value(&EnvSetArgs { value: pos }).unwrap()).await;
+            })
+        }
+    };
+
+    view! {
+        <div class="text-center">
+            <h1> "Filter Env" </h1>
+            <div class="border-4 rounded-md border-black text-center grid grid-cols-2">
+                <p> "amount" </p>
+                <Slider on_input=Box::new(slider("set_filter_env_amount")) start=SLIDER_MAX/>
+                <p> "attack" </p>
+                <Slider on_input=Box::new(slider("set_filter_env_atk"))/>
+                <p> "decay" </p>
+                <Slider on_input=Box::new(slider("set_filter_env_decay"))/>
+                <p> "sustain" </p>
+                <Slider on_input=Box::new(slider("set_filter_env_sustain"))/>
+                <p> "release" </p>
+                <Slider on_input=Box::new(slider("set_filter_env_release"))/>
+                <p> "key track" </p>
+                <Slider on_input=Box::new(slider("set_key_tracking"))/>
+                <p> "velocity" </p>
+                <Slider on_input=Box::new(slider("set_velocity_amount"))/>
             </div>
         </div>
     }
@@ -683,6 +1403,275 @@ fn Reverb() -> impl IntoView {
     }
 }
 
+#[component]
+fn MidiDevices() -> impl IntoView {
+    let (devices, set_devices) = signal(Vec::<(String, bool)>::new());
+
+    let refresh = move || {
+        spawn_local(async move {
+            set_devices.set(
+                from_value(invoke("list_midi_controllers", to_value(&Empty {}).unwrap()).await)
+                    .unwrap_or(Vec::new()),
+            );
+        })
+    };
+
+    let (virtual_port, set_virtual_port) = signal(None::<String>);
+
+    let refresh_virtual = move || {
+        spawn_local(async move {
+            set_virtual_port.set(
+                from_value(invoke("get_virtual_midi_port", to_value(&Empty {}).unwrap()).await)
+                    .unwrap_or(None),
+            );
+        })
+    };
+
+    let (mpe, set_mpe) = signal(false);
+
+    spawn_local(async move {
+        set_mpe.set(
+            from_value(invoke("get_mpe", to_value(&Empty {}).unwrap()).await).unwrap_or(false),
+        );
+    });
+
+    refresh();
+    refresh_virtual();
+
+    view! {
+        <div>
+            <h1> MIDI Devices </h1>
+            <div class="border-4 rounded-md border-black text-left p-4">
+                <div>
+                    "Virtual port: "
+                    <input type="text" placeholder="disabled"
+                        prop:value=move || virtual_port.get().unwrap_or_default()
+                        on:change=move |ev| {
+                            let name = event_target_value(&ev);
+
+                            spawn_local(async move {
+                                invoke("set_virtual_midi_port", to_value(&MidiDeviceArgs { name }).unwrap()).await;
+                                refresh_virtual();
+                            })
+                        }
+                    />
+                </div>
+                <button
+                    on:click=move |_| {
+                        let enabled = !mpe.get_untracked();
+                        set_mpe.set(enabled);
+
+                        spawn_local(async move {
+                            invoke("set_mpe", to_value(&MpeArgs { enabled }).unwrap()).await;
+                        })
+                    }
+                > { move || if mpe.get() { "MPE: on" } else { "MPE: off" } } </button>
+                <button on:click=move |_| refresh()> Refresh </button>
+                { move ||
+                    devices.get().into_iter().map(move |(name, connected)| {
+                        let label = if connected {
+                            format!("- [x] {name}")
+                        } else {
+                            format!("- [ ] {name}")
+                        };
+
+                        view! {
+                            <div>
+                                <button
+                                    on:click=move |_| {
+                                        let name = name.clone();
+
+                                        spawn_local(async move {
+                                            let cmd = if connected { "disconnect_midi" } else { "connect_midi" };
+                                            invoke(cmd, to_value(&MidiDeviceArgs { name }).unwrap()).await;
+                                            refresh();
+                                        });
+                                    }
+                                > { label } </button>
+                            </div>
+                        }
+                    }).collect::<Vec<_>>()
+                }
+            </div>
+        </div>
+    }
+}
+
+#[component]
+fn MidiFilePlayer() -> impl IntoView {
+    let (path, set_path) = signal(String::new());
+    let (status, set_status) = signal(String::from("no file loaded"));
+    let (looping, set_looping) = signal(false);
+
+    let load = move |_| {
+        spawn_local(async move {
+            let path = path.get_untracked();
+            let res = try_invoke(
+                "load_midi_file",
+                to_value(&MidiFileArgs { path: path.clone() }).unwrap(),
+            )
+            .await;
+
+            set_status.set(match res {
+                Ok(len) => format!("{:.1}s", from_value::<f32>(len).unwrap_or(0.0)),
+                Err(e) => format!(
+                    "failed to load {path}: {}",
+                    e.as_string().unwrap_or_default()
+                ),
+            });
+        })
+    };
+
+    view! {
+        <div>
+            <h1> MIDI File </h1>
+            <div class="border-4 rounded-md border-black text-left p-4">
+                <input type="text" placeholder="path/to/file.mid"
+                    on:change=move |ev| set_path.set(event_target_value(&ev))
+                />
+                <button on:click=load> Load </button>
+                <div> { move || status.get() } </div>
+                <button on:click=move |_| { spawn_local(async move { invoke("play_midi_file", to_value(&Empty {}).unwrap()).await; }) }> Play </button>
+                <button on:click=move |_| { spawn_local(async move { invoke("stop_midi_file", to_value(&Empty {}).unwrap()).await; }) }> Stop </button>
+                <button
+                    on:click=move |_| {
+                        let looping = !looping.get_untracked();
+                        set_looping.set(looping);
+
+                        spawn_local(async move {
+                            invoke("set_midi_file_loop", to_value(&MidiFileLoopArgs { looping }).unwrap()).await;
+                        })
+                    }
+                > { move || if looping.get() { "Loop: on" } else { "Loop: off" } } </button>
+                <div>
+                    "Seek (s): "
+                    <input type="number" min="0" step="0.1"
+                        on:change=move |ev| {
+                            let seconds = event_target_value(&ev).parse().unwrap_or(0.0);
+
+                            spawn_local(async move {
+                                invoke("seek_midi_file", to_value(&MidiFileSeekArgs { seconds }).unwrap()).await;
+                            })
+                        }
+                    />
+                </div>
+            </div>
+        </div>
+    }
+}
+
+#[component]
+fn MidiRecorder() -> impl IntoView {
+    let (path, set_path) = signal(String::from("take.mid"));
+    let (recording, set_recording) = signal(false);
+    let (status, set_status) = signal(String::new());
+
+    let toggle = move |_| {
+        let was_recording = recording.get_untracked();
+        set_recording.set(!was_recording);
+
+        spawn_local(async move {
+            if was_recording {
+                let path = path.get_untracked();
+                let res = try_invoke(
+                    "stop_recording",
+                    to_value(&MidiFileArgs { path: path.clone() }).unwrap(),
+                )
+                .await;
+
+                set_status.set(match res {
+                    Ok(n) => format!(
+                        "saved {} events to {path}",
+                        from_value::<usize>(n).unwrap_or(0)
+                    ),
+                    Err(e) => {
+                        // still recording, stopping again with another path saves the take.
+                        set_recording.set(true);
+                        format!(
+                            "failed to save {path}: {}",
+                            e.as_string().unwrap_or_default()
+                        )
+                    }
+                });
+            } else {
+                invoke("start_recording", to_value(&Empty {}).unwrap()).await;
+                set_status.set("recording...".to_string());
+            }
+        })
+    };
+
+    view! {
+        <div>
+            <h1> MIDI Recorder </h1>
+            <div class="border-4 rounded-md border-black text-left p-4">
+                <input type="text" prop:value=path
+                    on:change=move |ev| set_path.set(event_target_value(&ev))
+                />
+                <button on:click=toggle> { move || if recording.get() { "Stop" } else { "Record" } } </button>
+                <div> { move || status.get() } </div>
+            </div>
+        </div>
+    }
+}
+
+#[component]
+fn Tuning() -> impl IntoView {
+    let (status, set_status) = signal(String::from("12-TET"));
+
+    let load = move |cmd: &'static str, path: String| {
+        spawn_local(async move {
+            let res = invoke(cmd, to_value(&MidiFileArgs { path: path.clone() }).unwrap()).await;
+
+            set_status.set(if from_value(res).unwrap_or(false) {
+                format!("loaded {path}")
+            } else {
+                format!("failed to load {path}")
+            });
+        })
+    };
+
+    view! {
+        <div>
+            <h1> Tuning </h1>
+            <div class="border-4 rounded-md border-black text-left p-4">
+                <div>
+                    "A4 (Hz): "
+                    <input type="number" min="200" max="1000" step="0.1" value="440"
+                        on:change=move |ev| {
+                            let freq = event_target_value(&ev).parse().unwrap_or(440.0);
+
+                            spawn_local(async move {
+                                invoke("set_reference_pitch", to_value(&ReferencePitchArgs { freq }).unwrap()).await;
+                            })
+                        }
+                    />
+                </div>
+                <div>
+                    "Scale (.scl): "
+                    <input type="text" placeholder="path/to/scale.scl"
+                        on:change=move |ev| load("load_scale", event_target_value(&ev))
+                    />
+                </div>
+                <div>
+                    "Keyboard map (.kbm): "
+                    <input type="text" placeholder="path/to/map.kbm"
+                        on:change=move |ev| load("load_keyboard_map", event_target_value(&ev))
+                    />
+                </div>
+                <button
+                    on:click=move |_| {
+                        spawn_local(async move {
+                            invoke("reset_tuning", to_value(&Empty {}).unwrap()).await;
+                            set_status.set("12-TET".to_string());
+                        })
+                    }
+                > Reset </button>
+                <div> { move || status.get() } </div>
+            </div>
+        </div>
+    }
+}
+
 #[component]
 fn Connections() -> impl IntoView {
     let (connections, set_connections) =
@@ -957,6 +1946,10 @@ fn Connections() -> impl IntoView {
             // what's connected box
             <div>
                 <button on:click=re_con> Reconnect Midi </button>
+                <MidiDevices/>
+                <MidiFilePlayer/>
+                <MidiRecorder/>
+                <Tuning/>
                 <h1> Connected </h1>
                 <div class="border-4 rounded-md border-black justify-center text-center grid grid-cols-4 text-wrap">
                     // <For
@@ -1,8 +1,8 @@
-use crate::{notes::Note, Float, ModuleId, OscType};
+use crate::{mseg::Mseg, notes::Note, Float, ModuleId, OscType};
 use serde::{Deserialize, Serialize};
 
 /// commands that can be sent to the synth via uart or over a unix-socket
-#[derive(Deserialize, Serialize, PartialEq)]
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub enum SynthCmd {
     /// requests the entire, absolute state of the synth
     GetState,
@@ -34,6 +34,8 @@ pub enum SynthCmd {
     AdAtk(Float),
     /// sets the decay of the AD envelope filter
     AdDecay(Float),
+    /// sets the breakpoints of the MSEG envelope filter
+    SetMseg(Mseg),
     /// sets the attack of the ADSR envelope filter
     AdsrAtk(Float),
     /// sets the Decay of the ADSR envelope filter
@@ -60,9 +62,13 @@ pub enum SynthCmd {
     Disconnect(ModuleId, u8, ModuleId, u8),
     /// bend pitch by amount
     PitchBend(Float),
+    /// starts recording incoming MIDI
+    StartRecording,
+    /// stops recording MIDI and saves it as a MIDI file at the given path
+    StopRecording(String),
 }
 
-#[derive(Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug)]
 pub enum SynthId {
     /// the synth
     Synth,
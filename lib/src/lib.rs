@@ -3,7 +3,9 @@ use std::fmt::Display;
 use strum_macros::{EnumIter, EnumString};
 
 pub mod communication;
+pub mod mseg;
 pub mod notes;
+pub mod tuning;
 
 pub type ModuleId = u8;
 
@@ -32,6 +34,118 @@ pub enum OscType {
     Triangle,
     #[serde(alias = "saw-tooth", alias = "sawtooth", alias = "saw")]
     SawTooth,
+    #[serde(alias = "wavetable", alias = "wt")]
+    Wavetable,
+    #[serde(alias = "white", alias = "noise")]
+    WhiteNoise,
+    #[serde(alias = "pink")]
+    PinkNoise,
+    #[serde(alias = "brown", alias = "red")]
+    BrownNoise,
+}
+
+/// what an oscillator does when its sync input crosses zero upward.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, EnumIter, Hash, EnumString, Default,
+)]
+pub enum SyncMode {
+    /// restart the cycle.
+    #[default]
+    #[serde(alias = "hard")]
+    Hard,
+    /// reverse the direction of the cycle.
+    #[serde(alias = "soft")]
+    Soft,
+}
+
+/// the waveform of a sub oscillator.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, EnumIter, Hash, EnumString, Default,
+)]
+pub enum SubWaveform {
+    #[default]
+    #[serde(alias = "square", alias = "squ")]
+    Square,
+    #[serde(alias = "sine", alias = "sin")]
+    Sine,
+}
+
+/// the shape of an envelope segment.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, EnumIter, Hash, EnumString, Default,
+)]
+pub enum EnvCurve {
+    /// a straight ramp.
+    #[default]
+    #[serde(alias = "linear", alias = "lin")]
+    Linear,
+    /// moves fast at first then eases into the target, like an RC circuit.
+    #[serde(alias = "exponential", alias = "exp")]
+    Exponential,
+    /// starts slow then speeds up into the target.
+    #[serde(alias = "logarithmic", alias = "log")]
+    Logarithmic,
+}
+
+/// the response of the filter in an envelope filter.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, EnumIter, Hash, EnumString, Default,
+)]
+pub enum FilterMode {
+    /// the 4-pole Moog ladder lowpass (mixed with the dry signal).
+    #[default]
+    #[serde(alias = "moog")]
+    Moog,
+    #[serde(alias = "lowpass", alias = "lp")]
+    LowPass,
+    #[serde(alias = "highpass", alias = "hp")]
+    HighPass,
+    #[serde(alias = "bandpass", alias = "bp")]
+    BandPass,
+    #[serde(alias = "notch")]
+    Notch,
+}
+
+/// how steep a state-variable filter is.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, EnumIter, Hash, EnumString, Default,
+)]
+pub enum FilterSlope {
+    /// 12 dB per octave, one 2-pole stage.
+    #[default]
+    #[serde(alias = "12")]
+    Db12,
+    /// 24 dB per octave, two 2-pole stages.
+    #[serde(alias = "24")]
+    Db24,
+}
+
+/// what an envelope does when its gate opens again before it has finished.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, EnumIter, Hash, EnumString, Default,
+)]
+pub enum RetriggerMode {
+    /// drop to zero and start the attack again.
+    #[serde(alias = "reset")]
+    Reset,
+    /// start the attack again from the current level, like analogue envelopes.
+    #[default]
+    #[serde(alias = "restart")]
+    Restart,
+    /// don't restart the attack, carry on towards the sustain level.
+    #[serde(alias = "legato")]
+    Legato,
+}
+
+/// a segment of an envelope that can be given its own curve.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, EnumIter, Hash, EnumString)]
+pub enum EnvSegment {
+    #[serde(alias = "attack")]
+    Attack,
+    #[serde(alias = "decay")]
+    Decay,
+    #[serde(alias = "release")]
+    Release,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
@@ -56,7 +170,12 @@ pub enum ModuleType {
     OverDrive,
     Reverb,
     MCO, // Midi controlled Oscillator
-         // PMCO, // pollyphonic Midi Controlled Osc
+    // PMCO, // pollyphonic Midi Controlled Osc
+    Noise,
+    Envelope,
+    Filter,
+    Formant,
+    Follower,
 }
 
 impl Display for ModuleType {
@@ -72,6 +191,11 @@ impl Display for ModuleType {
             Self::OverDrive => write!(f, "OD"),
             Self::Reverb => write!(f, "Reverb"),
             Self::MCO => write!(f, "MCO"),
+            Self::Noise => write!(f, "Noise"),
+            Self::Envelope => write!(f, "Env"),
+            Self::Filter => write!(f, "VCF"),
+            Self::Formant => write!(f, "Formant"),
+            Self::Follower => write!(f, "Follower"),
             // Self::PMCO => write!(f, "PMCO"),
             // Self:: => write!(f, ""),
         }
@@ -80,22 +204,25 @@ impl Display for ModuleType {
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, EnumString, EnumIter, Hash, PartialEq, Eq)]
 pub enum FilterType {
-    // None,
-    // ADBDR,
+    None,
+    ADBDR,
     ADSR,
-    // AD,
-    // OC,
+    AD,
+    OC,
+    /// multi-stage breakpoint envelope, see `mseg::Mseg`.
+    MSEG,
     // TODO: add an AR filter
 }
 
-impl Into<Float> for FilterType {
-    fn into(self) -> Float {
-        match self {
-            // Self::None => 1.0,
-            // Self::ADBDR => 2.0,
-            Self::ADSR => 3.0,
-            // Self::OC => 4.0,
-            // Self::AD => 5.0,
+impl From<FilterType> for Float {
+    fn from(filter_type: FilterType) -> Self {
+        match filter_type {
+            FilterType::None => 1.0,
+            FilterType::ADBDR => 2.0,
+            FilterType::ADSR => 3.0,
+            FilterType::OC => 4.0,
+            FilterType::AD => 5.0,
+            FilterType::MSEG => 6.0,
         }
     }
 }
@@ -103,20 +230,19 @@ impl Into<Float> for FilterType {
 impl From<Float> for FilterType {
     fn from(value: Float) -> Self {
         match value {
-            // 1.0..2.0 => Self::None,
-            // 2.0..3.0 => Self::ADBDR,
+            1.0..2.0 => Self::None,
+            2.0..3.0 => Self::ADBDR,
             3.0..4.0 => Self::ADSR,
-            // 4.0..5.0 => Self::OC,
-            // 5.0..6.0 => Self::AD,
-            // _ => Self::None,
-            // _ => Self::OC,
+            4.0..5.0 => Self::OC,
+            5.0..6.0 => Self::AD,
+            6.0..7.0 => Self::MSEG,
             _ => Self::ADSR,
         }
     }
 }
 
-pub fn midi_to_freq(midi_note: u8) -> f32 {
-    let exp = (f32::from(midi_note) + 36.376_316) / 12.0;
-
-    2.0f32.powf(exp)
+/// returns the frequency of a MIDI note in the current tuning (12-TET with A4 = 440 Hz unless
+/// changed, see `tuning`).
+pub fn midi_to_freq(midi_note: u8) -> Float {
+    tuning::tuning().read().unwrap().freq(midi_note)
 }
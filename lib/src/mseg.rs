@@ -0,0 +1,309 @@
+//! multi-stage (breakpoint) envelope definitions.
+//!
+//! the text format has one breakpoint per line (or comma separated): a time, the level to reach
+//! by then and an optional curve (`lin`, `exp` or `log`). times are seconds, or beats when they
+//! end in `b`. a leading `*` marks the sustain point, `[` and `]` wrap the loop region and
+//! `tempo <bpm>` sets the tempo beat times are synced to. lines starting with `!` are comments.
+//!
+//! ```text
+//! 0.01 1
+//! 0.2 0.6 exp
+//! [0.25b 0.8
+//! 0.25b 0.6]
+//! 0.5 0 exp
+//! ```
+use crate::{EnvCurve, Float};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, fs, path::Path};
+
+/// the tempo beat times are synced to when none is set, in BPM.
+pub const DEFAULT_TEMPO: Float = 120.0;
+
+#[derive(Debug)]
+pub enum MsegError {
+    Io(std::io::Error),
+    Parse(String),
+}
+
+impl Display for MsegError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to read MSEG file: {e}"),
+            Self::Parse(e) => write!(f, "failed to parse MSEG: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for MsegError {}
+
+impl From<std::io::Error> for MsegError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// how long a segment takes.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum MsegTime {
+    Seconds(Float),
+    /// synced to the tempo of the envelope.
+    Beats(Float),
+}
+
+impl MsegTime {
+    /// returns the length in seconds at `tempo` BPM.
+    pub fn seconds(&self, tempo: Float) -> Float {
+        match *self {
+            Self::Seconds(s) => s,
+            Self::Beats(b) => b * 60.0 / tempo,
+        }
+    }
+}
+
+/// the end of a segment, the envelope reaches `level` after `time` following `curve`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Breakpoint {
+    pub time: MsegTime,
+    pub level: Float,
+    pub curve: EnvCurve,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Mseg {
+    /// the segments, in order. the envelope starts from its current level (0 when idle).
+    pub points: Vec<Breakpoint>,
+    /// the breakpoint the envelope holds at while the gate is open.
+    pub sustain: Option<usize>,
+    /// the first and last breakpoint of the region that repeats while the gate is open.
+    pub loop_region: Option<(usize, usize)>,
+    /// tempo in BPM that beat times are synced to.
+    pub tempo: Float,
+}
+
+impl Default for Mseg {
+    /// a plain ADSR shape.
+    fn default() -> Self {
+        let point = |time, level, curve| Breakpoint {
+            time: MsegTime::Seconds(time),
+            level,
+            curve,
+        };
+
+        Self {
+            points: vec![
+                point(0.01, 1.0, EnvCurve::Linear),
+                point(0.1, 0.8, EnvCurve::Exponential),
+                point(0.3, 0.0, EnvCurve::Exponential),
+            ],
+            sustain: Some(1),
+            loop_region: None,
+            tempo: DEFAULT_TEMPO,
+        }
+    }
+}
+
+impl Mseg {
+    /// reads an MSEG definition from a file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MsegError> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// the index of the first breakpoint played after the gate closes.
+    pub fn release_start(&self) -> usize {
+        let sustain = self.sustain.map(|i| i + 1).unwrap_or(0);
+        let loop_end = self.loop_region.map(|(_, end)| end + 1).unwrap_or(0);
+
+        sustain.max(loop_end)
+    }
+}
+
+impl std::str::FromStr for Mseg {
+    type Err = MsegError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut mseg = Self {
+            points: Vec::new(),
+            sustain: None,
+            loop_region: None,
+            tempo: DEFAULT_TEMPO,
+        };
+        let mut loop_start = None;
+
+        let entries = s
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('!'))
+            .flat_map(|line| line.split(','))
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty());
+
+        for entry in entries {
+            if let Some(tempo) = entry.strip_prefix("tempo") {
+                mseg.tempo = tempo
+                    .trim()
+                    .parse()
+                    .ok()
+                    .filter(|tempo: &Float| *tempo > 0.0)
+                    .ok_or(MsegError::Parse(format!("invalid tempo: {entry}")))?;
+                continue;
+            }
+
+            let i = mseg.points.len();
+            let mut point = entry;
+
+            if let Some(rest) = point.strip_prefix('[') {
+                loop_start = Some(i);
+                point = rest.trim_start();
+            }
+
+            if let Some(rest) = point.strip_prefix('*') {
+                mseg.sustain = Some(i);
+                point = rest.trim_start();
+            }
+
+            if let Some(rest) = point.strip_suffix(']') {
+                let start = loop_start.take().ok_or(MsegError::Parse(format!(
+                    "loop end without a start: {entry}"
+                )))?;
+                mseg.loop_region = Some((start, i));
+                point = rest.trim_end();
+            }
+
+            mseg.points.push(parse_point(point)?);
+        }
+
+        if mseg.points.is_empty() {
+            return Err(MsegError::Parse("no breakpoints".into()));
+        }
+
+        if loop_start.is_some() {
+            return Err(MsegError::Parse("loop start without an end".into()));
+        }
+
+        Ok(mseg)
+    }
+}
+
+/// parses a breakpoint like "0.25b 0.6 exp".
+fn parse_point(point: &str) -> Result<Breakpoint, MsegError> {
+    let err = || MsegError::Parse(format!("invalid breakpoint: {point}"));
+    let mut tokens = point.split_whitespace();
+
+    let time = tokens.next().ok_or_else(err)?;
+    let time = match time.strip_suffix('b') {
+        Some(beats) => MsegTime::Beats(beats.parse().map_err(|_| err())?),
+        None => MsegTime::Seconds(time.parse().map_err(|_| err())?),
+    };
+    let level: Float = tokens
+        .next()
+        .and_then(|level| level.parse().ok())
+        .ok_or_else(err)?;
+    let curve = match tokens.next().map(|curve| curve.to_lowercase()).as_deref() {
+        None | Some("lin") | Some("linear") => EnvCurve::Linear,
+        Some("exp") | Some("exponential") => EnvCurve::Exponential,
+        Some("log") | Some("logarithmic") => EnvCurve::Logarithmic,
+        Some(_) => return Err(err()),
+    };
+
+    if time.seconds(DEFAULT_TEMPO) < 0.0 || !(0.0..=1.0).contains(&level) || tokens.next().is_some()
+    {
+        return Err(err());
+    }
+
+    Ok(Breakpoint { time, level, curve })
+}
+
+impl Display for Mseg {
+    /// writes the text format, so the output can be parsed again.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut points = self.points.iter().enumerate().map(|(i, point)| {
+            let time = match point.time {
+                MsegTime::Seconds(s) => format!("{s}"),
+                MsegTime::Beats(b) => format!("{b}b"),
+            };
+            let curve = match point.curve {
+                EnvCurve::Linear => "lin",
+                EnvCurve::Exponential => "exp",
+                EnvCurve::Logarithmic => "log",
+            };
+            let loop_start = if self.loop_region.is_some_and(|(start, _)| start == i) {
+                "["
+            } else {
+                ""
+            };
+            let sustain = if self.sustain == Some(i) { "*" } else { "" };
+            let loop_end = if self.loop_region.is_some_and(|(_, end)| end == i) {
+                "]"
+            } else {
+                ""
+            };
+
+            format!(
+                "{loop_start}{sustain}{time} {} {curve}{loop_end}",
+                point.level
+            )
+        });
+
+        write!(f, "tempo {}", self.tempo)?;
+        points.try_for_each(|point| write!(f, ", {point}"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TEXT: &str = "! a looping envelope
+tempo 90
+0.01 1
+*0.2 0.6 exp
+[0.25b 0.8, 0.5b 0.6 log]
+0.5 0 exp";
+
+    #[test]
+    fn parse() {
+        let mseg: Mseg = TEXT.parse().unwrap();
+
+        assert_eq!(mseg.tempo, 90.0);
+        assert_eq!(mseg.points.len(), 5);
+        assert_eq!(mseg.sustain, Some(1));
+        assert_eq!(mseg.loop_region, Some((2, 3)));
+        assert_eq!(mseg.points[2].time, MsegTime::Beats(0.25));
+        assert_eq!(mseg.points[3].curve, EnvCurve::Logarithmic);
+        assert_eq!(mseg.points[4].curve, EnvCurve::Exponential);
+        assert_eq!(mseg.release_start(), 4);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mseg: Mseg = TEXT.parse().unwrap();
+        let again: Mseg = mseg.to_string().parse().unwrap();
+
+        assert_eq!(mseg, again);
+        assert_eq!(
+            Mseg::default(),
+            Mseg::default().to_string().parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn beats_follow_tempo() {
+        assert_eq!(MsegTime::Beats(1.0).seconds(120.0), 0.5);
+        assert_eq!(MsegTime::Seconds(1.0).seconds(120.0), 1.0);
+    }
+
+    #[test]
+    fn parse_errors() {
+        for text in [
+            "",
+            "tempo 0, 0.1 1",
+            "0.1",
+            "0.1 2",
+            "-0.1 1",
+            "0.1 1 wobble",
+            "[0.1 1",
+            "0.1 1]",
+        ] {
+            assert!(text.parse::<Mseg>().is_err(), "{text:?} should not parse");
+        }
+    }
+}
@@ -0,0 +1,591 @@
+use crate::Float;
+use std::{
+    fmt::Display,
+    fs,
+    path::Path,
+    sync::{OnceLock, RwLock},
+};
+
+/// the default reference pitch of A4 (MIDI note 69) in Hz.
+pub const DEFAULT_A4: f64 = 440.0;
+/// MIDI note number of A4.
+pub const A4_NOTE: u8 = 69;
+/// MIDI note number of middle C.
+pub const MIDDLE_C: u8 = 60;
+/// MIDI Tuning Standard sub-ID#1 of SysEx messages.
+const MTS_SUB_ID: u8 = 0x08;
+/// a channel mask with every MIDI channel set, bit n is channel n (0 based).
+pub const ALL_CHANNELS: u16 = 0xFFFF;
+
+#[derive(Debug)]
+pub enum TuningError {
+    Io(std::io::Error),
+    Parse(String),
+}
+
+impl Display for TuningError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to read tuning file: {e}"),
+            Self::Parse(e) => write!(f, "failed to parse tuning file: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for TuningError {}
+
+impl From<std::io::Error> for TuningError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// a scale as described by a Scala `.scl` file. every degree is stored in cents above the first
+/// one, the last degree is the period (usually the octave).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scale {
+    pub description: String,
+    pub cents: Vec<f64>,
+}
+
+impl Default for Scale {
+    /// 12 tone equal temperament
+    fn default() -> Self {
+        Self::equal(12)
+    }
+}
+
+impl Scale {
+    /// makes an `n` tone equal temperament scale, ie. `Scale::equal(19)` for 19-EDO.
+    pub fn equal(n: usize) -> Self {
+        let n = n.max(1);
+
+        Self {
+            description: format!("{n} tone equal temperament"),
+            cents: (1..=n).map(|i| 1200.0 * i as f64 / n as f64).collect(),
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TuningError> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// cents above degree 0 of `degree`, which can be past the period or negative.
+    pub fn degree_cents(&self, degree: i32) -> f64 {
+        let len = self.cents.len() as i32;
+        let period = self.cents[self.cents.len() - 1];
+        let step = degree.rem_euclid(len);
+        let octave = degree.div_euclid(len);
+
+        octave as f64 * period
+            + if step == 0 {
+                0.0
+            } else {
+                self.cents[step as usize - 1]
+            }
+    }
+}
+
+impl std::str::FromStr for Scale {
+    type Err = TuningError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines().filter(|line| !line.starts_with('!'));
+
+        let description = lines
+            .next()
+            .ok_or(TuningError::Parse("missing description".into()))?
+            .trim()
+            .to_string();
+        let n: usize = lines
+            .next()
+            .and_then(|line| line.split_whitespace().next())
+            .and_then(|n| n.parse().ok())
+            .ok_or(TuningError::Parse("missing number of notes".into()))?;
+
+        let cents = lines
+            .filter_map(|line| line.split_whitespace().next())
+            .take(n)
+            .map(parse_pitch)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if cents.len() != n || n == 0 {
+            return Err(TuningError::Parse(format!(
+                "expected {n} notes but found {}",
+                cents.len()
+            )));
+        }
+
+        Ok(Self { description, cents })
+    }
+}
+
+/// parses a scala pitch, either cents (has a '.') or a ratio like "3/2" or "2".
+fn parse_pitch(pitch: &str) -> Result<f64, TuningError> {
+    let err = || TuningError::Parse(format!("invalid pitch: {pitch}"));
+
+    if pitch.contains('.') {
+        return pitch.parse().map_err(|_| err());
+    }
+
+    let (num, den) = pitch.split_once('/').unwrap_or((pitch, "1"));
+    let num: f64 = num.parse().map_err(|_| err())?;
+    let den: f64 = den.parse().map_err(|_| err())?;
+
+    if num <= 0.0 || den <= 0.0 {
+        return Err(err());
+    }
+
+    Ok(1200.0 * (num / den).log2())
+}
+
+/// maps MIDI notes onto scale degrees, as described by a Scala `.kbm` file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyboardMap {
+    /// the lowest note that will be tuned.
+    pub first_note: u8,
+    /// the highest note that will be tuned.
+    pub last_note: u8,
+    /// the note that plays degree 0 of the scale.
+    pub middle_note: u8,
+    /// the note that plays `reference_freq`.
+    pub reference_note: u8,
+    pub reference_freq: f64,
+    /// the scale degree the mapping repeats at, 0 means the scale's period.
+    pub octave_degree: usize,
+    /// the scale degree of each key of the mapping, `None` for keys that don't sound. empty means
+    /// every key plays the next scale degree.
+    pub keys: Vec<Option<i32>>,
+}
+
+impl Default for KeyboardMap {
+    fn default() -> Self {
+        Self {
+            first_note: 0,
+            last_note: 127,
+            middle_note: MIDDLE_C,
+            reference_note: A4_NOTE,
+            reference_freq: DEFAULT_A4,
+            octave_degree: 0,
+            keys: Vec::new(),
+        }
+    }
+}
+
+impl KeyboardMap {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TuningError> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// the scale degree `note` plays, `None` if it is unmapped.
+    pub fn degree(&self, note: u8, scale_len: usize) -> Option<i32> {
+        if note < self.first_note || note > self.last_note {
+            return None;
+        }
+
+        let offset = note as i32 - self.middle_note as i32;
+
+        if self.keys.is_empty() {
+            return Some(offset);
+        }
+
+        let size = self.keys.len() as i32;
+        let octave_degree = if self.octave_degree == 0 {
+            scale_len
+        } else {
+            self.octave_degree
+        };
+
+        self.keys[offset.rem_euclid(size) as usize]
+            .map(|degree| offset.div_euclid(size) * octave_degree as i32 + degree)
+    }
+}
+
+impl std::str::FromStr for KeyboardMap {
+    type Err = TuningError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut values = s
+            .lines()
+            .filter(|line| !line.starts_with('!'))
+            .filter_map(|line| line.split_whitespace().next());
+        let mut next = |what: &str| {
+            values
+                .next()
+                .ok_or(TuningError::Parse(format!("missing {what}")))
+        };
+        let int = |value: &str, what: &str| {
+            value
+                .parse::<usize>()
+                .map_err(|_| TuningError::Parse(format!("invalid {what}: {value}")))
+        };
+        let note = |value: &str, what: &str| {
+            int(value, what).and_then(|n| {
+                u8::try_from(n)
+                    .ok()
+                    .filter(|n| *n < 128)
+                    .ok_or(TuningError::Parse(format!("{what} is out of range: {n}")))
+            })
+        };
+
+        let size = int(next("map size")?, "map size")?;
+        let first_note = note(next("first note")?, "first note")?;
+        let last_note = note(next("last note")?, "last note")?;
+        let middle_note = note(next("middle note")?, "middle note")?;
+        let reference_note = note(next("reference note")?, "reference note")?;
+        let reference_freq = next("reference frequency")?;
+        let reference_freq: f64 = reference_freq.parse().map_err(|_| {
+            TuningError::Parse(format!("invalid reference frequency: {reference_freq}"))
+        })?;
+        let octave_degree = int(next("octave degree")?, "octave degree")?;
+
+        let keys = (0..size)
+            .map(|_| {
+                let key = next("key mapping")?;
+
+                if key == "x" {
+                    Ok(None)
+                } else {
+                    int(key, "key mapping").map(|degree| Some(degree as i32))
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            first_note,
+            last_note,
+            middle_note,
+            reference_note,
+            reference_freq,
+            octave_degree,
+            keys,
+        })
+    }
+}
+
+/// the frequency of every MIDI note, built from a scale and keyboard mapping and adjustable with
+/// MIDI Tuning Standard messages.
+#[derive(Debug, Clone)]
+pub struct Tuning {
+    pub scale: Scale,
+    pub map: KeyboardMap,
+    freqs: [Float; 128],
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Self::new(Scale::default(), KeyboardMap::default())
+    }
+}
+
+impl Tuning {
+    pub fn new(scale: Scale, map: KeyboardMap) -> Self {
+        let mut tuning = Self {
+            scale,
+            map,
+            freqs: [0.0; 128],
+        };
+        tuning.retune();
+
+        tuning
+    }
+
+    /// returns the frequency of `note`, 0.0 for notes the keyboard map leaves unmapped.
+    pub fn freq(&self, note: u8) -> Float {
+        self.freqs[note as usize & 127]
+    }
+
+    /// sets the frequency of the reference note (A4 unless a keyboard map says otherwise).
+    pub fn set_reference(&mut self, freq: f64) {
+        self.map.reference_freq = freq;
+        self.retune();
+    }
+
+    pub fn set_scale(&mut self, scale: Scale) {
+        self.scale = scale;
+        self.retune();
+    }
+
+    pub fn set_map(&mut self, map: KeyboardMap) {
+        self.map = map;
+        self.retune();
+    }
+
+    /// recalculates every note from the scale and keyboard mapping, dropping any MTS changes.
+    fn retune(&mut self) {
+        let len = self.scale.cents.len();
+        // the reference note may be unmapped, so fall back to it playing degree 0.
+        let ref_cents = self
+            .scale
+            .degree_cents(self.map.degree(self.map.reference_note, len).unwrap_or(0));
+
+        for note in 0..128 {
+            self.freqs[note as usize] = match self.map.degree(note, len) {
+                Some(degree) => {
+                    let cents = self.scale.degree_cents(degree) - ref_cents;
+                    (self.map.reference_freq * (cents / 1200.0).exp2()) as Float
+                }
+                None => 0.0,
+            };
+        }
+    }
+
+    /// applies a MIDI Tuning Standard SysEx message (without the leading 0xF0 and trailing 0xF7).
+    /// supports single note tuning changes, bulk tuning dumps and 1 byte scale/octave tuning.
+    /// `channels` is the mask of channels the synth listens on (bit n is channel n), scale/octave
+    /// messages that don't target any of them are ignored. returns false if the message isn't
+    /// applied.
+    ///
+    /// scale/octave tuning retunes every note to 12 tone equal temperament plus the offset of its
+    /// pitch class, around the keyboard map's reference note and frequency. it replaces the loaded
+    /// scale, but notes a `.kbm` leaves unmapped stay silent. loading a scale or keyboard map again
+    /// drops it.
+    pub fn apply_sysex(&mut self, data: &[u8], channels: u16) -> bool {
+        // universal (non-)real time, device id, sub id 1, sub id 2
+        let [kind @ (0x7E | 0x7F), _device, MTS_SUB_ID, sub_id, body @ ..] = data else {
+            return false;
+        };
+
+        match (kind, sub_id) {
+            // bulk tuning dump: program, 16 char name, 128 * 3 bytes of frequency data, checksum
+            (0x7E, 0x01) if body.len() >= 1 + 16 + 128 * 3 => {
+                body[17..17 + 128 * 3]
+                    .chunks_exact(3)
+                    .enumerate()
+                    .for_each(|(note, freq)| self.set_mts_freq(note as u8, freq));
+            }
+            // single note tuning change: program, count, [note, frequency data]
+            (0x7F, 0x02) if !body.is_empty() => self.single_note_changes(&body[1..]),
+            // single note tuning change with bank: bank, program, count, [note, frequency data]
+            (_, 0x07) if body.len() >= 2 => self.single_note_changes(&body[2..]),
+            // scale/octave tuning, 1 byte form: 3 channel mask bytes, 12 offsets in cents from
+            // equal temperament around the current reference pitch
+            (_, 0x08) if body.len() >= 3 + 12 => {
+                if mts_channels(&body[..3]) & channels == 0 {
+                    return false;
+                }
+
+                let offsets = &body[3..3 + 12];
+                let reference_note = self.map.reference_note as f64;
+                let len = self.scale.cents.len();
+
+                for note in 0..128_u8 {
+                    if self.map.degree(note, len).is_none() {
+                        continue;
+                    }
+
+                    let cents = (note as f64 - reference_note) * 100.0
+                        + offsets[note as usize % 12] as f64
+                        - 64.0;
+                    self.freqs[note as usize] =
+                        (self.map.reference_freq * (cents / 1200.0).exp2()) as Float;
+                }
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    fn single_note_changes(&mut self, body: &[u8]) {
+        let Some((count, changes)) = body.split_first() else {
+            return;
+        };
+
+        changes
+            .chunks_exact(4)
+            .take(*count as usize)
+            .for_each(|change| self.set_mts_freq(change[0], &change[1..]));
+    }
+
+    /// sets the frequency of `note` from the 3 byte MTS frequency format, semitone then a 14 bit
+    /// fraction of a semitone. 0x7F 0x7F 0x7F means "no change".
+    fn set_mts_freq(&mut self, note: u8, freq: &[u8]) {
+        let [semitone, msb, lsb] = [freq[0], freq[1], freq[2]];
+
+        if note > 127 || [semitone, msb, lsb] == [0x7F, 0x7F, 0x7F] {
+            return;
+        }
+
+        let fraction = ((msb as u16) << 7 | lsb as u16) as f64 / 16384.0;
+        let semitones = semitone as f64 + fraction - A4_NOTE as f64;
+
+        self.freqs[note as usize] = (DEFAULT_A4 * (semitones / 12.0).exp2()) as Float;
+    }
+}
+
+/// decodes the 3 byte MTS channel mask (channels 15-16, 8-14, 1-7) into a mask where bit n is
+/// channel n (0 based).
+fn mts_channels(mask: &[u8]) -> u16 {
+    (mask[0] as u16 & 0x03) << 14 | (mask[1] as u16 & 0x7F) << 7 | mask[2] as u16 & 0x7F
+}
+
+/// the tuning used by `midi_to_freq`.
+pub fn tuning() -> &'static RwLock<Tuning> {
+    static TUNING: OnceLock<RwLock<Tuning>> = OnceLock::new();
+
+    TUNING.get_or_init(|| RwLock::new(Tuning::default()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn close(a: Float, b: Float) -> bool {
+        (a - b).abs() < 0.01
+    }
+
+    #[test]
+    fn default_is_12_tet() {
+        let tuning = Tuning::default();
+
+        assert!(close(tuning.freq(A4_NOTE), 440.0));
+        assert!(close(tuning.freq(A4_NOTE + 12), 880.0));
+        assert!(close(tuning.freq(MIDDLE_C), 261.63));
+    }
+
+    #[test]
+    fn parse_scale() {
+        let scale: Scale = "! pythagorean.scl
+!
+a fifths based scale
+ 3
+!
+ 9/8
+ 701.955
+ 2
+"
+        .parse()
+        .unwrap();
+
+        assert_eq!(scale.description, "a fifths based scale");
+        assert_eq!(scale.cents.len(), 3);
+        assert!((scale.cents[0] - 203.91).abs() < 0.01);
+        assert!((scale.cents[2] - 1200.0).abs() < 1e-9);
+        assert!((scale.degree_cents(4) - 1403.91).abs() < 0.01);
+        assert!((scale.degree_cents(-1) + 498.045).abs() < 0.01);
+        assert!("too short\n 2\n 100.0\n".parse::<Scale>().is_err());
+        assert!("bad pitch\n 1\n 0/2\n".parse::<Scale>().is_err());
+    }
+
+    #[test]
+    fn parse_keyboard_map() {
+        let map: KeyboardMap = "! white keys only
+3
+0
+127
+60
+69
+432.0
+7
+0
+x
+1
+"
+        .parse()
+        .unwrap();
+
+        assert_eq!(map.middle_note, 60);
+        assert_eq!(map.reference_note, 69);
+        assert_eq!(map.reference_freq, 432.0);
+        assert_eq!(map.keys, vec![Some(0), None, Some(1)]);
+        assert_eq!(map.degree(60, 12), Some(0));
+        assert_eq!(map.degree(61, 12), None);
+        assert_eq!(map.degree(63, 12), Some(7));
+        assert_eq!(map.degree(59, 12), Some(-6));
+        assert!("1\n0\n128\n60\n69\n440.0\n0\n0\n"
+            .parse::<KeyboardMap>()
+            .is_err());
+    }
+
+    #[test]
+    fn unmapped_keys_are_silent() {
+        let map = KeyboardMap {
+            keys: vec![Some(0), None],
+            ..KeyboardMap::default()
+        };
+        let tuning = Tuning::new(Scale::default(), map);
+
+        assert_eq!(tuning.freq(MIDDLE_C + 1), 0.0);
+        assert!(tuning.freq(MIDDLE_C) > 0.0);
+    }
+
+    #[test]
+    fn set_reference() {
+        let mut tuning = Tuning::default();
+        tuning.set_reference(432.0);
+
+        assert!(close(tuning.freq(A4_NOTE), 432.0));
+        assert!(close(tuning.freq(A4_NOTE - 12), 216.0));
+    }
+
+    #[test]
+    fn mts_single_note() {
+        let mut tuning = Tuning::default();
+        // note 60 to 69 + half a semitone
+        let msg = [0x7F, 0x00, MTS_SUB_ID, 0x02, 0x00, 0x01, 60, 69, 0x40, 0x00];
+
+        assert!(tuning.apply_sysex(&msg, ALL_CHANNELS));
+        assert!(close(tuning.freq(60), 440.0 * (0.5 / 12.0 as Float).exp2()));
+        assert!(close(tuning.freq(61), 277.18));
+        // "no change"
+        assert!(tuning.apply_sysex(
+            &[0x7F, 0x00, MTS_SUB_ID, 0x02, 0x00, 0x01, 61, 0x7F, 0x7F, 0x7F],
+            ALL_CHANNELS
+        ));
+        assert!(close(tuning.freq(61), 277.18));
+        assert!(!tuning.apply_sysex(&[0x7E, 0x00, 0x06, 0x01], ALL_CHANNELS));
+    }
+
+    #[test]
+    fn mts_scale_octave_follows_reference() {
+        let mut tuning = Tuning::default();
+        tuning.set_reference(432.0);
+
+        let mut msg = vec![0x7E, 0x00, MTS_SUB_ID, 0x08, 0x03, 0x7F, 0x7F];
+        // every note in tune except A, 50 cents sharp
+        msg.extend((0..12).map(|i| if i == 9 { 64 + 50 } else { 64 }));
+
+        assert!(tuning.apply_sysex(&msg, ALL_CHANNELS));
+        assert!(close(
+            tuning.freq(A4_NOTE),
+            432.0 * (50.0 / 1200.0 as Float).exp2()
+        ));
+        assert!(close(
+            tuning.freq(A4_NOTE + 3),
+            432.0 * (3.0 / 12.0 as Float).exp2()
+        ));
+    }
+
+    #[test]
+    fn mts_scale_octave_channels() {
+        let mut tuning = Tuning::default();
+        // channel 16 only
+        let mut msg = vec![0x7F, 0x00, MTS_SUB_ID, 0x08, 0x02, 0x00, 0x00];
+        msg.extend([64 + 50; 12]);
+
+        assert!(!tuning.apply_sysex(&msg, 0x7FFF));
+        assert!(close(tuning.freq(A4_NOTE), 440.0));
+        assert!(tuning.apply_sysex(&msg, 1 << 15));
+        assert!(close(
+            tuning.freq(A4_NOTE),
+            440.0 * (50.0 / 1200.0 as Float).exp2()
+        ));
+    }
+
+    #[test]
+    fn mts_scale_octave_keeps_unmapped_keys_silent() {
+        let map = KeyboardMap {
+            first_note: 21,
+            last_note: 108,
+            ..KeyboardMap::default()
+        };
+        let mut tuning = Tuning::new(Scale::default(), map);
+        let mut msg = vec![0x7F, 0x00, MTS_SUB_ID, 0x08, 0x03, 0x7F, 0x7F];
+        msg.extend([64; 12]);
+
+        assert!(tuning.apply_sysex(&msg, ALL_CHANNELS));
+        assert_eq!(tuning.freq(20), 0.0);
+        assert!(close(tuning.freq(MIDDLE_C), 261.63));
+    }
+}
@@ -13,7 +13,17 @@ mod communications;
 
 fn start_midi(synth: Arc<Controller>) -> Result<MIDIControls> {
     let mut midi_con = MIDIControls::new(synth)?;
-    midi_con.connect_default()?;
+
+    if let Err(e) = midi_con.open_virtual_port() {
+        warn!("{e}");
+    }
+
+    // devices in `SYNTH_8080_MIDI_DEVICES` get picked up by the watcher once plugged in.
+    if let Err(e) = midi_con.connect_default() {
+        warn!("no midi device connected: {e}");
+    }
+
+    midi_con.start_watcher();
 
     Ok(midi_con)
 }
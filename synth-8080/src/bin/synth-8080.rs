@@ -1,13 +1,13 @@
 #![feature(exclusive_range_pattern, let_chains)]
-use anyhow::{bail, Result};
+use anyhow::{Result, bail};
 use tokio::spawn;
 use tracing::*;
 
 #[cfg(feature = "hardware")]
 use synth_8080::controller::hardware::HardwareControls;
 use synth_8080::{
-    self, chorus, controller::midi::MIDIControls, default_modules, envelope, mk_synth,
-    start_logging, vco, AudioGen,
+    self, AudioGen, chorus, controller::midi::MIDIControls, default_modules, envelope, mk_synth,
+    start_logging, vco,
 };
 
 // pub type Float = f32;
@@ -294,7 +294,16 @@ pub async fn main() -> Result<()> {
             bail!("No MIDI for you! {e}");
         }
         Ok(mut midi_con) => {
-            midi_con.connect_default()?;
+            if let Err(e) = midi_con.open_virtual_port() {
+                warn!("{e}");
+            }
+
+            // devices in `SYNTH_8080_MIDI_DEVICES` get picked up by the watcher once plugged in.
+            if let Err(e) = midi_con.connect_default() {
+                warn!("no midi device connected: {e}");
+            }
+
+            midi_con.start_watcher();
             info!("MIDI started");
             midi_con
         }
@@ -307,8 +316,13 @@ pub async fn main() -> Result<()> {
     // let audio_out_thread = {
     {
         let audio_out_thread = spawn(audio_gen);
-        // #[cfg(feature = "hardware")]
-        // HardwareControls::new(ctrlr.clone())?.await;
+
+        // notes, recording and MSEG commands from the micro-controller come in over uart.
+        match HardwareControls::new(ctrlr.clone()) {
+            Ok(hardware) => _ = spawn(hardware),
+            Err(e) => warn!("no serial controller connected: {e}"),
+        }
+
         audio_out_thread.await;
         // audio_out_thread
     };
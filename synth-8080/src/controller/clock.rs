@@ -0,0 +1,117 @@
+use crate::Float;
+use std::time::Instant;
+
+/// MIDI clock pulses per beat.
+pub const PULSES_PER_BEAT: u32 = 24;
+/// how far (in BPM) the clock's tempo has to move before it is passed on, keeps jitter out.
+pub const TEMPO_THRESHOLD: Float = 0.5;
+
+/// works out the tempo of an incoming MIDI clock.
+#[derive(Debug, Default)]
+pub struct MidiClock {
+    /// when the first pulse of the current beat came in, `None` until the clock runs.
+    beat_start: Option<Instant>,
+    /// pulses since `beat_start`.
+    pulses: u32,
+    /// the last tempo that was passed on, in BPM.
+    tempo: Option<Float>,
+}
+
+impl MidiClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// counts a clock pulse. once per beat, returns the tempo in BPM if it moved by at least
+    /// `TEMPO_THRESHOLD`.
+    pub fn pulse(&mut self) -> Option<Float> {
+        self.pulse_at(Instant::now())
+    }
+
+    fn pulse_at(&mut self, now: Instant) -> Option<Float> {
+        let Some(start) = self.beat_start else {
+            self.beat_start = Some(now);
+            return None;
+        };
+
+        self.pulses += 1;
+
+        if self.pulses < PULSES_PER_BEAT {
+            return None;
+        }
+
+        let beat = now.duration_since(start).as_secs_f64() as Float;
+        self.beat_start = Some(now);
+        self.pulses = 0;
+
+        if beat <= 0.0 {
+            return None;
+        }
+
+        let tempo = 60.0 / beat;
+
+        if self
+            .tempo
+            .is_some_and(|last| (last - tempo).abs() < TEMPO_THRESHOLD)
+        {
+            return None;
+        }
+
+        self.tempo = Some(tempo);
+
+        Some(tempo)
+    }
+
+    /// forgets the beat in progress, used when the clock starts or stops.
+    pub fn reset(&mut self) {
+        self.beat_start = None;
+        self.pulses = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    /// sends a beat of pulses `beat` apart, starting at `start`.
+    fn beat(clock: &mut MidiClock, start: Instant, beat: Duration) -> Option<Float> {
+        (1..=PULSES_PER_BEAT)
+            .map(|i| clock.pulse_at(start + beat * i / PULSES_PER_BEAT))
+            .last()
+            .flatten()
+    }
+
+    #[test]
+    fn tempo() {
+        let mut clock = MidiClock::new();
+        let start = Instant::now();
+
+        assert_eq!(clock.pulse_at(start), None);
+
+        let tempo = beat(&mut clock, start, Duration::from_millis(500)).unwrap();
+        assert!((tempo - 120.0).abs() < 0.01);
+
+        // jitter under the threshold isn't passed on
+        let start = start + Duration::from_millis(500);
+        assert_eq!(beat(&mut clock, start, Duration::from_millis(501)), None);
+
+        let start = start + Duration::from_millis(501);
+        let tempo = beat(&mut clock, start, Duration::from_millis(600)).unwrap();
+        assert!((tempo - 100.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn reset() {
+        let mut clock = MidiClock::new();
+        let start = Instant::now();
+
+        clock.pulse_at(start);
+        clock.pulse_at(start + Duration::from_millis(10));
+        clock.reset();
+
+        // the first pulse after a reset only starts the beat
+        assert_eq!(clock.pulse_at(start + Duration::from_secs(5)), None);
+        assert_eq!(clock.pulses, 0);
+    }
+}
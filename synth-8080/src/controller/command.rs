@@ -0,0 +1,27 @@
+use super::Controller;
+use lib::communication::command::SynthCmd;
+use tracing::*;
+
+impl Controller {
+    /// applies a command received from the micro-controller (see `hardware::HardwareControls`).
+    pub fn handle_cmd(&self, cmd: SynthCmd) -> anyhow::Result<()> {
+        match cmd {
+            SynthCmd::Play(note) => self.play(note),
+            SynthCmd::Stop(note) => self.stop(note),
+            SynthCmd::Connect(src_module, src_output, dest_module, dest_input) => {
+                self.connect(src_module, src_output, dest_module, dest_input)?
+            }
+            SynthCmd::Disconnect(src_module, src_output, dest_module, dest_input) => {
+                self.disconnect(src_module, src_output, dest_module, dest_input)?
+            }
+            SynthCmd::SetMseg(mseg) => self.set_mseg(mseg),
+            SynthCmd::StartRecording => self.start_recording(),
+            SynthCmd::StopRecording(path) => {
+                self.stop_recording(path)?;
+            }
+            cmd => warn!("{cmd:?} is not handled yet"),
+        }
+
+        Ok(())
+    }
+}
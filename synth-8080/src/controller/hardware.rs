@@ -1,4 +1,4 @@
-use super::Note;
+use lib::{communication::command::SynthCmd, tuning::A4_NOTE};
 use serialport::SerialPort;
 use std::{
     future::Future,
@@ -50,14 +50,15 @@ impl Future for HardwareControls {
 
                 info!("received command: {cmd:?}");
 
-                if cmd == "play" {
-                    info!("setting Notes");
-                    self.controller.play(Note::A4);
-                } else if cmd == "stop" {
-                    self.controller.stop(Note::A4);
-                } else if cmd == "power-off" {
+                if cmd == "power-off" {
                     cx.waker().wake_by_ref();
                     return Poll::Ready(());
+                } else if let Some(cmd) = parse_cmd(cmd) {
+                    if let Err(e) = self.controller.handle_cmd(cmd) {
+                        error!("{e}");
+                    }
+                } else if !cmd.is_empty() {
+                    warn!("unknown command: {cmd:?}");
                 }
             }
             Err(ref e) if e.kind() == io::ErrorKind::TimedOut => (),
@@ -68,3 +69,47 @@ impl Future for HardwareControls {
         Poll::Pending
     }
 }
+
+/// turns a text command from the micro-controller into a `SynthCmd`. besides "play" and "stop",
+/// "record" arms the MIDI recorder, "stop-recording <path>" saves the take to `path` and
+/// "mseg <breakpoints>" sets the MSEG (see `lib::mseg`, breakpoints separated by commas).
+fn parse_cmd(cmd: &str) -> Option<SynthCmd> {
+    let (name, arg) = cmd
+        .split_once(' ')
+        .map_or((cmd, ""), |(name, arg)| (name, arg.trim()));
+
+    match name {
+        "play" => Some(SynthCmd::Play(A4_NOTE)),
+        "stop" => Some(SynthCmd::Stop(A4_NOTE)),
+        "record" => Some(SynthCmd::StartRecording),
+        "stop-recording" if !arg.is_empty() => Some(SynthCmd::StopRecording(arg.into())),
+        "mseg" => arg
+            .parse()
+            .map(SynthCmd::SetMseg)
+            .map_err(|e| warn!("{e}"))
+            .ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(parse_cmd("play"), Some(SynthCmd::Play(A4_NOTE)));
+        assert_eq!(parse_cmd("record"), Some(SynthCmd::StartRecording));
+        assert_eq!(
+            parse_cmd("stop-recording /tmp/take.mid"),
+            Some(SynthCmd::StopRecording("/tmp/take.mid".into()))
+        );
+        assert_eq!(parse_cmd("stop-recording"), None);
+        assert!(matches!(
+            parse_cmd("mseg 0.01 1, *0.2 0.5 exp, 0.3 0"),
+            Some(SynthCmd::SetMseg(mseg)) if mseg.sustain == Some(1)
+        ));
+        assert_eq!(parse_cmd("mseg 0.1"), None);
+        assert_eq!(parse_cmd("dance"), None);
+    }
+}
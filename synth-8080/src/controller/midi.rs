@@ -1,208 +1,564 @@
-use super::Note;
+use super::{Controller, Note};
+use crate::{Float, midi_osc::MidiOsc};
 use anyhow::bail;
+use lib::tuning::{ALL_CHANNELS, tuning};
 use midir::{Ignore, MidiInput, MidiInputConnection, MidiInputPort};
-use midly::{MidiMessage, live::LiveEvent};
+use midly::{
+    MidiMessage,
+    live::{LiveEvent, SystemCommon, SystemRealtime},
+    num::u7,
+};
 use std::{
-    future::Future,
-    io,
-    process::exit,
-    str::FromStr,
-    sync::{Arc, Mutex},
-    task::Poll,
+    env,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
 };
 use tracing::*;
 
+/// environment variable holding a comma separated list of MIDI devices to connect to whenever they
+/// show up.
+pub const AUTO_CONNECT_VAR: &str = "SYNTH_8080_MIDI_DEVICES";
+/// environment variable holding the name of the virtual MIDI input port the synth publishes. set it
+/// to an empty string to not publish one.
+pub const VIRTUAL_PORT_VAR: &str = "SYNTH_8080_MIDI_VIRTUAL_PORT";
+/// name of the virtual MIDI input port if `VIRTUAL_PORT_VAR` isn't set.
+pub const DEFAULT_VIRTUAL_PORT: &str = "synth-8080";
+/// how often the hotplug watcher rescans the MIDI ports.
+pub const HOTPLUG_INTERVAL: Duration = Duration::from_secs(1);
+
+pub const CC_DATA_ENTRY: u8 = 6;
+pub const CC_VOLUME: u8 = 7;
+pub const CC_RESONANCE: u8 = 71;
+pub const CC_CUTOFF: u8 = 74;
+pub const CC_ALL_SOUND_OFF: u8 = 120;
+pub const CC_RPN_LSB: u8 = 100;
+pub const CC_RPN_MSB: u8 = 101;
+pub const CC_ALL_NOTES_OFF: u8 = 123;
+
+/// open MIDI connections, keyed by the name of the port they are connected to.
+type MidiCons = Arc<Mutex<Vec<(String, MidiInputConnection<()>)>>>;
+
 pub struct MIDIControls {
-    controller: Arc<super::Controller>,
-    /// MIDI connection to the micro-controller
-    midi_cons: Vec<MidiInputConnection<()>>,
-    // midi_in: MidiInput,
+    controller: Arc<Controller>,
+    /// MIDI connections to keyboards/controllers
+    midi_cons: MidiCons,
+    /// device names that should be connected whenever they are plugged in.
+    auto_connect: Arc<Mutex<Vec<String>>>,
+    /// keeps the hotplug watcher thread running, set to false to stop it.
+    watching: Arc<AtomicBool>,
+    watcher: Option<JoinHandle<()>>,
+    /// name of the virtual input port to publish, `None` if it is disabled.
+    virtual_port_name: Option<String>,
+    /// the virtual input port other programs can connect to, kept apart from `midi_cons` because
+    /// it never shows up when listing ports.
+    virtual_con: Option<MidiInputConnection<()>>,
 }
 
 impl MIDIControls {
-    pub fn new(controller: Arc<super::Controller>) -> anyhow::Result<Self> {
+    pub fn new(controller: Arc<Controller>) -> anyhow::Result<Self> {
         info!("making midi controller");
 
+        let auto_connect: Vec<String> = env::var(AUTO_CONNECT_VAR)
+            .unwrap_or_default()
+            .split(',')
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .collect();
+
+        if !auto_connect.is_empty() {
+            info!("will auto-connect to midi devices: {auto_connect:?}");
+        }
+
+        let virtual_port_name = match env::var(VIRTUAL_PORT_VAR) {
+            Ok(name) if name.trim().is_empty() => None,
+            Ok(name) => Some(name.trim().to_string()),
+            Err(_) => Some(DEFAULT_VIRTUAL_PORT.to_string()),
+        };
+
         Ok(Self {
             controller,
-            midi_cons: Vec::new(),
-            // midi_in,
+            midi_cons: Arc::new(Mutex::new(Vec::new())),
+            auto_connect: Arc::new(Mutex::new(auto_connect)),
+            watching: Arc::new(AtomicBool::new(false)),
+            watcher: None,
+            virtual_port_name,
+            virtual_con: None,
         })
     }
 
-    fn mk_midi_input(&mut self) -> anyhow::Result<MidiInput> {
-        let mut midi_in = MidiInput::new("midir reading input")?;
-        midi_in.ignore(Ignore::None);
-        info!("midi_in made");
+    pub fn list_ports(&mut self) -> anyhow::Result<Vec<(MidiInputPort, String)>> {
+        list_ports()
+    }
 
-        Ok(midi_in)
+    /// returns the names of the ports that are currently connected.
+    pub fn connected_ports(&self) -> Vec<String> {
+        self.midi_cons
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect()
     }
 
-    pub fn list_ports(&mut self) -> anyhow::Result<Vec<(MidiInputPort, String)>> {
-        let mut midi_in = self.mk_midi_input()?;
+    pub fn connect_to_port(&mut self, in_port: MidiInputPort) -> anyhow::Result<()> {
+        let in_port_name = mk_midi_input()?.port_name(&in_port)?;
+
+        if self.connected_ports().contains(&in_port_name) {
+            bail!("already connected to midi port: {in_port_name}");
+        }
+
+        let midi_con = open_connection(self.controller.clone(), &in_port, &in_port_name)?;
+        self.midi_cons
+            .lock()
+            .unwrap()
+            .push((in_port_name, midi_con));
+
+        Ok(())
+    }
 
-        // Get an input port (read from console if multiple are available)
-        Ok(midi_in
-            .ports()
+    /// connects to the port called `name` and remembers the device so it gets reconnected if it is
+    /// unplugged and plugged back in.
+    pub fn connect_by_name(&mut self, name: &str) -> anyhow::Result<()> {
+        let Some((port, port_name)) = list_ports()?
             .into_iter()
-            .map(|port| Ok((port.clone(), midi_in.port_name(&port)?)))
-            .collect::<anyhow::Result<Vec<(MidiInputPort, String)>>>()?)
+            .find(|(_, port_name)| port_name == name || device_name(port_name) == name)
+        else {
+            bail!("no midi port named: {name}");
+        };
+
+        self.connect_to_port(port)?;
+
+        let mut auto_connect = self.auto_connect.lock().unwrap();
+        let device = device_name(&port_name).to_string();
+
+        if !auto_connect.contains(&device) {
+            auto_connect.push(device);
+        }
+
+        Ok(())
     }
 
-    pub fn connect_to_port(&mut self, in_port: MidiInputPort) -> anyhow::Result<()> {
-        let mut midi_in = self.mk_midi_input()?;
-
-        let in_port_name = midi_in.port_name(&in_port)?;
-        info!("Opening connection to midi port: {in_port_name}");
-        let ctrlr = self.controller.clone();
-
-        // _conn_in needs to be a named parameter, because it needs to be kept alive until the end of the scope
-        let midi_con = midi_in
-            .connect(
-                &in_port,
-                "midir-read-input",
-                // &in_port_name,
-                move |_stamp, message, _| {
-                    // info!("{}: {:?} (len = {})", stamp, message, message.len());
-                    let event = LiveEvent::parse(message).unwrap();
-
-                    match event {
-                        LiveEvent::Midi {
-                            channel: _,
-                            message,
-                        } => match message {
-                            MidiMessage::NoteOn { key, vel } => {
-                                // info!("hit note {} on channel {}", key, channel);
-                                let note = Note::from(u8::from(key));
-                                // if let Ok(note) = Note::from(&key) {
-                                trace!("playing {note}");
-                                ctrlr.modules.lock().unwrap().mco[0].play_note(note);
-                                // } else {
-                                //     error!("{}", key.to_string());
-                                // }
-                            }
-                            MidiMessage::NoteOff { key, vel } => {
-                                // info!("released note {} on channel {}", key, channel);
-                                let note = Note::from(u8::from(key));
-                                // if let Ok(note) = Note::from(&key) {
-                                trace!("stopping {note}");
-                                ctrlr.modules.lock().unwrap().mco[0].stop_note(note);
-                                // } else {
-                                //     error!("{}", key.to_string());
-                                // }
-                            }
-                            _ => {}
-                        },
-                        _ => {}
-                    }
-                    // trace!("concluded midi function.")
-                },
-                (),
-            )
-            .map_or_else(|e| bail!("midi connection error: {e}"), |con| Ok(con))?;
-        // info!("midi connected");
-
-        self.midi_cons.push(midi_con);
-        info!("Connected to midi port: {in_port_name}");
+    /// closes the connection to the port called `name` and stops auto-connecting to it.
+    pub fn disconnect(&mut self, name: &str) -> anyhow::Result<()> {
+        let device = device_name(name).to_string();
+        self.auto_connect
+            .lock()
+            .unwrap()
+            .retain(|wanted| !device.contains(wanted.as_str()));
+
+        let mut cons = self.midi_cons.lock().unwrap();
+
+        let Some(i) = cons
+            .iter()
+            .position(|(port_name, _)| port_name == name || device_name(port_name) == name)
+        else {
+            bail!("not connected to midi port: {name}");
+        };
+
+        let (port_name, midi_con) = cons.remove(i);
+        midi_con.close();
+        info!("Disconnected from midi port: {port_name}");
 
         Ok(())
     }
 
-    pub fn connect_default(&mut self) -> anyhow::Result<()> {
-        // info!("making midi controller");
-        // let mut midi_in = MidiInput::new("midir reading input")?;
-        // midi_in.ignore(Ignore::None);
-        // info!("midi_in made");
-        //
-        // // Get an input port (read from console if multiple are available)
-        // let in_ports = midi_in.ports();
-        // info!("in_ports => {}", in_ports.len());
-        let ports = self.list_ports()?;
-
-        if ports.len() > 1 {
-            let in_port = ports[1].0.clone();
-            self.connect_to_port(in_port)?;
-        } else {
-            let in_port = ports[0].0.clone();
-            self.connect_to_port(in_port)?;
-        }
-        // .into_iter()
-        // .map(|port| self.connect_to_port(port.0))
-        // .collect::<anyhow::Result<()>>()?;
-
-        // let in_port = match in_ports.len() {
-        //     0 => bail!("no input port found"),
-        //     1 => {
-        //         info!(
-        //             "Choosing the only available input port: {}",
-        //             midi_in.port_name(&in_ports[0]).unwrap()
-        //         );
-        //         &in_ports[0]
-        //     }
-        //     2 => {
-        //         info!(
-        //             "Choosing the second available input port: {}",
-        //             midi_in.port_name(&in_ports[1]).unwrap()
-        //         );
-        //         &in_ports[1]
-        //     }
-        //     _ => bail!("too many midi devices. exiting."),
-        // };
-
-        // info!("Opening connection");
-        // let in_port_name = midi_in.port_name(in_port)?;
-        // let ctrlr = self.controller.clone();
-        //
-        // // _conn_in needs to be a named parameter, because it needs to be kept alive until the end of the scope
-        // let midi_con = midi_in
-        //     .connect(
-        //         in_port,
-        //         "midir-read-input",
-        //         // &in_port_name,
-        //         move |stamp, message, _| {
-        //             info!("{}: {:?} (len = {})", stamp, message, message.len());
-        //             let event = LiveEvent::parse(message).unwrap();
-        //
-        //             match event {
-        //                 LiveEvent::Midi { channel, message } => match message {
-        //                     MidiMessage::NoteOn { key, vel } => {
-        //                         info!("hit note {} on channel {}", key, channel);
-        //                         let note = Note::from(u8::from(key));
-        //                         // if let Ok(note) = Note::from(&key) {
-        //                         info!("playing {note}");
-        //                         ctrlr.modules.lock().unwrap().mco[0].play_note(note);
-        //                         // } else {
-        //                         //     error!("{}", key.to_string());
-        //                         // }
-        //                     }
-        //                     MidiMessage::NoteOff { key, vel } => {
-        //                         info!("released note {} on channel {}", key, channel);
-        //                         let note = Note::from(u8::from(key));
-        //                         // if let Ok(note) = Note::from(&key) {
-        //                         info!("stopping {note}");
-        //                         ctrlr.modules.lock().unwrap().mco[0].stop_note(note);
-        //                         // } else {
-        //                         //     error!("{}", key.to_string());
-        //                         // }
-        //                     }
-        //                     _ => {}
-        //                 },
-        //                 _ => {}
-        //             }
-        //             // trace!("concluded midi function.")
-        //         },
-        //         (),
-        //     )
-        //     .map_or_else(|e| bail!("midi connection error: {e}"), |con| Ok(con))?;
-        // info!("midi connected");
-        //
-        // self.midi_cons.push(midi_con);
+    /// disconnects from every MIDI port.
+    pub fn disconnect_all(&mut self) {
+        self.midi_cons
+            .lock()
+            .unwrap()
+            .drain(..)
+            .for_each(|(_, midi_con)| {
+                midi_con.close();
+            });
+    }
+
+    /// publishes the virtual input port so other programs on this machine (DAWs, sequencers, etc)
+    /// can send MIDI to the synth. does nothing if the virtual port is disabled or already open.
+    pub fn open_virtual_port(&mut self) -> anyhow::Result<()> {
+        let Some(name) = self.virtual_port_name.clone() else {
+            return Ok(());
+        };
+
+        if self.virtual_con.is_some() {
+            return Ok(());
+        }
+
+        self.virtual_con = Some(open_virtual_connection(self.controller.clone(), &name)?);
+
+        Ok(())
+    }
+
+    /// removes the virtual input port.
+    pub fn close_virtual_port(&mut self) {
+        if let Some(midi_con) = self.virtual_con.take() {
+            midi_con.close();
+            info!("closed virtual midi port");
+        }
+    }
+
+    /// renames the virtual input port, `None` disables it. the port is reopened under the new name
+    /// if it was open.
+    pub fn set_virtual_port_name(&mut self, name: Option<String>) -> anyhow::Result<()> {
+        let was_open = self.virtual_con.is_some();
+
+        self.close_virtual_port();
+        self.virtual_port_name = name.filter(|name| !name.is_empty());
+
+        if was_open {
+            self.open_virtual_port()?;
+        }
 
         Ok(())
     }
 
-    // fn handle_midi_in(&self, message: &[u8]) {
-    //         }
+    /// returns the name of the virtual input port if it is open.
+    pub fn virtual_port(&self) -> Option<String> {
+        self.virtual_con
+            .as_ref()
+            .and(self.virtual_port_name.clone())
+    }
+
+    /// turns MPE mode on or off. MPE controllers normally configure their zones themselves with the
+    /// MPE configuration message, this is for ones that don't.
+    pub fn set_mpe(&mut self, enabled: bool) {
+        self.controller.modules.lock().unwrap().mco[0].set_mpe(enabled);
+    }
+
+    /// returns true if MPE mode is on.
+    pub fn mpe(&self) -> bool {
+        self.controller.modules.lock().unwrap().mco[0].mpe.enabled()
+    }
+
+    /// sets the list of device names that get connected to whenever they are plugged in. a port
+    /// matches if its name contains one of these.
+    pub fn set_auto_connect(&mut self, devices: Vec<String>) {
+        *self.auto_connect.lock().unwrap() = devices;
+    }
+
+    /// returns the list of device names that get connected to whenever they are plugged in.
+    pub fn auto_connect(&self) -> Vec<String> {
+        self.auto_connect.lock().unwrap().clone()
+    }
+
+    /// drops connections to ports that have gone away and connects to any auto-connect devices
+    /// that have shown up.
+    pub fn refresh(&mut self) -> anyhow::Result<()> {
+        refresh(&self.controller, &self.midi_cons, &self.auto_connect)
+    }
+
+    /// connects to the auto-connect devices, falling back to the first port that isn't a "through"
+    /// port if none of them are plugged in.
+    pub fn connect_default(&mut self) -> anyhow::Result<()> {
+        self.refresh()?;
+
+        if !self.midi_cons.lock().unwrap().is_empty() {
+            return Ok(());
+        }
+
+        let ports = list_ports()?;
+
+        let Some((_, port_name)) = ports
+            .iter()
+            .find(|(_, port_name)| !port_name.contains("Through"))
+            .or(ports.first())
+        else {
+            bail!("no midi input ports found");
+        };
+
+        self.connect_by_name(&port_name.clone())
+    }
+
+    /// starts a background thread that reconnects auto-connect devices when they are plugged back
+    /// in.
+    pub fn start_watcher(&mut self) {
+        if self.watching.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let controller = self.controller.clone();
+        let midi_cons = self.midi_cons.clone();
+        let auto_connect = self.auto_connect.clone();
+        let watching = self.watching.clone();
+
+        self.watcher = Some(thread::spawn(move || {
+            while watching.load(Ordering::SeqCst) {
+                if let Err(e) = refresh(&controller, &midi_cons, &auto_connect) {
+                    error!("midi hotplug watcher failed to rescan ports: {e}");
+                }
+
+                // woken early by `stop_watcher`.
+                thread::park_timeout(HOTPLUG_INTERVAL);
+            }
+        }));
+
+        info!("midi hotplug watcher started");
+    }
+
+    /// stops the hotplug watcher thread.
+    pub fn stop_watcher(&mut self) {
+        self.watching.store(false, Ordering::SeqCst);
+
+        if let Some(watcher) = self.watcher.take() {
+            watcher.thread().unpark();
+            _ = watcher.join();
+        }
+    }
+}
+
+impl Drop for MIDIControls {
+    fn drop(&mut self) {
+        self.stop_watcher();
+    }
+}
+
+fn mk_midi_input() -> anyhow::Result<MidiInput> {
+    let mut midi_in = MidiInput::new("midir reading input")?;
+    midi_in.ignore(Ignore::None);
+
+    Ok(midi_in)
+}
+
+fn list_ports() -> anyhow::Result<Vec<(MidiInputPort, String)>> {
+    let midi_in = mk_midi_input()?;
+
+    midi_in
+        .ports()
+        .into_iter()
+        .map(|port| Ok((port.clone(), midi_in.port_name(&port)?)))
+        .collect()
+}
+
+/// strips the ALSA "client:port" numbers off the end of a port name, those change when a device
+/// gets plugged back in.
+fn device_name(port_name: &str) -> &str {
+    match port_name.rsplit_once(' ') {
+        Some((device, numbers))
+            if numbers.contains(':') && numbers.chars().all(|c| c.is_ascii_digit() || c == ':') =>
+        {
+            device
+        }
+        _ => port_name,
+    }
+}
+
+fn open_connection(
+    controller: Arc<Controller>,
+    in_port: &MidiInputPort,
+    in_port_name: &str,
+) -> anyhow::Result<MidiInputConnection<()>> {
+    info!("Opening connection to midi port: {in_port_name}");
+
+    let midi_con = mk_midi_input()?
+        .connect(
+            in_port,
+            "midir-read-input",
+            on_message(controller, in_port_name),
+            (),
+        )
+        .map_or_else(|e| bail!("midi connection error: {e}"), Ok)?;
+
+    info!("Connected to midi port: {in_port_name}");
+
+    Ok(midi_con)
+}
+
+#[cfg(unix)]
+fn open_virtual_connection(
+    controller: Arc<Controller>,
+    name: &str,
+) -> anyhow::Result<MidiInputConnection<()>> {
+    use midir::os::unix::VirtualInput;
+
+    let mut midi_in = MidiInput::new(name)?;
+    midi_in.ignore(Ignore::None);
+
+    let midi_con = midi_in
+        .create_virtual(name, on_message(controller, name), ())
+        .map_or_else(|e| bail!("failed to create virtual midi port: {e}"), Ok)?;
+
+    info!("Opened virtual midi port: {name}");
+
+    Ok(midi_con)
+}
+
+#[cfg(not(unix))]
+fn open_virtual_connection(
+    _controller: Arc<Controller>,
+    _name: &str,
+) -> anyhow::Result<MidiInputConnection<()>> {
+    bail!("virtual midi ports are not supported on this platform");
+}
+
+/// makes the callback for a MIDI connection, records and then plays incoming messages.
+fn on_message(
+    controller: Arc<Controller>,
+    port_name: &str,
+) -> impl FnMut(u64, &[u8], &mut ()) + Send + 'static {
+    let port_name = port_name.to_string();
+
+    move |stamp, message, _| {
+        controller
+            .recorder
+            .lock()
+            .unwrap()
+            .record(&port_name, stamp, message);
+        handle_message(&controller, message)
+    }
+}
+
+fn refresh(
+    controller: &Arc<Controller>,
+    midi_cons: &MidiCons,
+    auto_connect: &Mutex<Vec<String>>,
+) -> anyhow::Result<()> {
+    let ports = list_ports()?;
+    let wanted = auto_connect.lock().unwrap().clone();
+
+    let connected: Vec<String> = {
+        let mut cons = midi_cons.lock().unwrap();
+
+        // midir doesn't tell us when a device goes away so drop connections to ports that are gone.
+        cons.retain(|(name, _)| {
+            let present = ports.iter().any(|(_, port_name)| port_name == name);
+
+            if !present {
+                warn!("midi port {name} went away");
+            }
+
+            present
+        });
+
+        cons.iter().map(|(name, _)| name.clone()).collect()
+    };
+
+    // opening a port can be slow, so it is done without holding the connections lock.
+    let new_cons: Vec<_> = ports
+        .into_iter()
+        .filter(|(_, port_name)| {
+            wanted
+                .iter()
+                .any(|device| device_name(port_name).contains(device.as_str()))
+                && !connected.contains(port_name)
+        })
+        .filter_map(|(port, port_name)| {
+            match open_connection(controller.clone(), &port, &port_name) {
+                Ok(midi_con) => Some((port_name, midi_con)),
+                Err(e) => {
+                    error!("failed to auto-connect to {port_name}: {e}");
+                    None
+                }
+            }
+        })
+        .collect();
+
+    let mut cons = midi_cons.lock().unwrap();
+
+    for (port_name, midi_con) in new_cons {
+        // it may have been connected by hand while the port was being opened.
+        if !cons.iter().any(|(name, _)| *name == port_name) {
+            cons.push((port_name, midi_con));
+        }
+    }
+
+    Ok(())
+}
+
+/// applies a raw MIDI message to the synth.
+pub fn handle_message(ctrlr: &Controller, message: &[u8]) {
+    let event = match LiveEvent::parse(message) {
+        Ok(event) => event,
+        Err(e) => {
+            warn!("failed to parse midi message {message:?}: {e}");
+            return;
+        }
+    };
+
+    match event {
+        LiveEvent::Midi { channel, message } => apply_message(
+            &mut ctrlr.modules.lock().unwrap().mco[0],
+            channel.as_int(),
+            message,
+        ),
+        LiveEvent::Common(SystemCommon::SysEx(data)) => {
+            // notes on every channel are played, so any channel a message targets is ours.
+            if tuning()
+                .write()
+                .unwrap()
+                .apply_sysex(u7::slice_as_int(data), ALL_CHANNELS)
+            {
+                info!("applied MIDI tuning standard message");
+                ctrlr.modules.lock().unwrap().mco[0].retune();
+            }
+        }
+        LiveEvent::Realtime(SystemRealtime::TimingClock) => {
+            let tempo = ctrlr.clock.lock().unwrap().pulse();
+
+            if let Some(tempo) = tempo {
+                debug!("following MIDI clock at {tempo:.1} BPM");
+                ctrlr.set_tempo(tempo);
+            }
+        }
+        LiveEvent::Realtime(SystemRealtime::Start | SystemRealtime::Stop) => {
+            ctrlr.clock.lock().unwrap().reset()
+        }
+        _ => {}
+    }
+}
+
+/// applies a channel message to an MCO, used by both live MIDI input and MIDI file playback. when
+/// MPE is on, bend, pressure and timbre (CC 74) on member channels only affect the notes played on
+/// that channel.
+pub fn apply_message(mco: &mut MidiOsc, channel: u8, message: MidiMessage) {
+    let member = mco.mpe.is_member(channel);
+
+    match message {
+        MidiMessage::NoteOn { key, vel } if vel > 0 => {
+            let note = Note::from(u8::from(key));
+            let velocity = vel.as_int() as Float / 127.0;
+            trace!("playing {note}");
+
+            if let Err(e) = mco.play_note_with_velocity(channel, note, velocity) {
+                debug!("{e}");
+            }
+        }
+        MidiMessage::NoteOn { key, vel: _ } | MidiMessage::NoteOff { key, vel: _ } => {
+            let note = Note::from(u8::from(key));
+            trace!("stopping {note}");
+
+            if let Err(e) = mco.stop_note_on(channel, note) {
+                debug!("{e}");
+            }
+        }
+        MidiMessage::Controller { controller, value } => {
+            let raw = value.as_int();
+            let value = raw as Float / 127.0;
+
+            match controller.as_int() {
+                CC_RPN_MSB => mco.mpe.select_rpn_msb(channel, raw),
+                CC_RPN_LSB => mco.mpe.select_rpn_lsb(channel, raw),
+                CC_DATA_ENTRY => mco.mpe.data_entry(channel, raw),
+                CC_VOLUME => mco.set_volume(value),
+                CC_RESONANCE => mco.set_resonance(value),
+                CC_CUTOFF if member => mco.set_channel_timbre(channel, value),
+                CC_CUTOFF => mco.set_cutoff(value),
+                CC_ALL_SOUND_OFF | CC_ALL_NOTES_OFF => mco.stop_all(),
+                cc => trace!("unhandled midi CC {cc}"),
+            }
+        }
+        MidiMessage::ChannelAftertouch { vel } if member => {
+            mco.set_channel_pressure(channel, vel.as_int() as Float / 127.0)
+        }
+        MidiMessage::PitchBend { bend } if member => {
+            mco.set_channel_bend(channel, bend.as_f32() as Float)
+        }
+        MidiMessage::PitchBend { bend } => mco.set_pitch_bend(bend.as_f32() as Float),
+        _ => {}
+    }
 }
 
 // impl Future for HardwareControls {
@@ -0,0 +1,405 @@
+use crate::{Float, SAMPLE_RATE};
+use anyhow::{Result, bail};
+use midly::{Format, MetaMessage, MidiMessage, Smf, Timing, TrackEventKind, num::u7};
+use std::{fs, path::Path};
+use tracing::*;
+
+/// default tempo of a MIDI file that doesn't set one, in micro seconds per beat (120 BPM).
+pub const DEFAULT_TEMPO: u32 = 500_000;
+
+/// a channel message keyed by the sample it should be played at.
+type Event = (u64, u8, MidiMessage);
+/// a tempo (micro seconds per beat) keyed by the sample it starts at.
+type TempoChange = (u64, u32);
+
+/// plays Standard MIDI Files, stepped once per sample from the audio loop.
+#[derive(Debug, Default)]
+pub struct MidiPlayer {
+    /// channel messages keyed by the sample they should be played at, sorted by sample.
+    events: Vec<Event>,
+    /// tempo changes, sorted by sample.
+    tempos: Vec<TempoChange>,
+    /// index of the next tempo change.
+    next_tempo: usize,
+    /// set when the tempo changes, cleared by `take_tempo_change`.
+    tempo_changed: bool,
+    /// length of the loaded file in samples.
+    length: u64,
+    /// current playback position in samples.
+    position: u64,
+    /// index of the next event to be played.
+    next: usize,
+    playing: bool,
+    looping: bool,
+    /// notes started by the player that have not been released yet.
+    sounding: Vec<(u8, u7)>,
+}
+
+impl MidiPlayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// loads the MIDI file at `path`, replacing whatever was loaded. playback is stopped and
+    /// rewound, notes still sounding are released through `dispatch`.
+    pub fn load(
+        &mut self,
+        path: impl AsRef<Path>,
+        dispatch: impl FnMut(u8, MidiMessage),
+    ) -> Result<()> {
+        let path = path.as_ref();
+        let bytes = fs::read(path)?;
+        let smf = Smf::parse(&bytes)?;
+
+        if smf.header.format == Format::Sequential {
+            bail!("sequential (format 2) MIDI files are not supported");
+        }
+
+        let (events, tempos, length) = schedule(&smf);
+
+        self.stop(dispatch);
+        self.events = events;
+        self.tempos = tempos;
+        self.length = length;
+        self.rewind();
+        info!(
+            "loaded {} events from {}, {:.2}s long",
+            self.events.len(),
+            path.display(),
+            self.length()
+        );
+
+        Ok(())
+    }
+
+    /// starts (or resumes) playback.
+    pub fn play(&mut self) {
+        if self.events.is_empty() {
+            warn!("no MIDI file loaded, nothing to play");
+            return;
+        }
+
+        if self.position >= self.length {
+            self.rewind();
+        }
+
+        self.playing = true;
+    }
+
+    /// stops playback, notes still sounding are released through `dispatch`.
+    pub fn stop(&mut self, dispatch: impl FnMut(u8, MidiMessage)) {
+        self.playing = false;
+        self.release(dispatch);
+    }
+
+    /// when on, playback starts over from the beginning when the end of the file is reached.
+    pub fn set_loop(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+
+    /// jumps to `seconds` into the file, notes still sounding are released through `dispatch`.
+    pub fn seek(&mut self, seconds: Float, dispatch: impl FnMut(u8, MidiMessage)) {
+        self.release(dispatch);
+        self.position = ((seconds.max(0.0) * SAMPLE_RATE as Float) as u64).min(self.length);
+        self.next = self
+            .events
+            .partition_point(|(at, _, _)| *at < self.position);
+        self.next_tempo = self.tempos.partition_point(|(at, _)| *at < self.position);
+        self.tempo_changed = true;
+    }
+
+    /// goes back to the start of the file.
+    fn rewind(&mut self) {
+        self.position = 0;
+        self.next = 0;
+        self.next_tempo = 0;
+        self.tempo_changed = true;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn is_looping(&self) -> bool {
+        self.looping
+    }
+
+    /// current position in seconds.
+    pub fn position(&self) -> Float {
+        self.position as Float / SAMPLE_RATE as Float
+    }
+
+    /// length of the loaded file in seconds.
+    pub fn length(&self) -> Float {
+        self.length as Float / SAMPLE_RATE as Float
+    }
+
+    /// the tempo at the current position in BPM.
+    pub fn tempo(&self) -> Float {
+        let i = self.tempos.partition_point(|(at, _)| *at <= self.position);
+        let tempo = i.checked_sub(1).map_or(DEFAULT_TEMPO, |i| self.tempos[i].1);
+
+        60_000_000.0 / tempo as Float
+    }
+
+    /// returns the tempo in BPM if it changed since the last call.
+    pub fn take_tempo_change(&mut self) -> Option<Float> {
+        std::mem::take(&mut self.tempo_changed).then(|| self.tempo())
+    }
+
+    /// advances playback by one sample, sending every event that is due to `dispatch`.
+    pub fn step(&mut self, mut dispatch: impl FnMut(u8, MidiMessage)) {
+        if !self.playing {
+            return;
+        }
+
+        while let Some((at, channel, message)) = self.events.get(self.next).copied() {
+            if at > self.position {
+                break;
+            }
+
+            self.track(channel, message);
+            dispatch(channel, message);
+            self.next += 1;
+        }
+
+        while let Some((at, _)) = self.tempos.get(self.next_tempo) {
+            if *at > self.position {
+                break;
+            }
+
+            self.next_tempo += 1;
+            self.tempo_changed = true;
+        }
+
+        self.position += 1;
+
+        if self.position > self.length {
+            self.release(dispatch);
+            self.rewind();
+            self.playing = self.looping;
+        }
+    }
+
+    /// keeps track of which notes are sounding so they can be released on stop/seek.
+    fn track(&mut self, channel: u8, message: MidiMessage) {
+        match message {
+            MidiMessage::NoteOn { key, vel } if vel > 0 => self.sounding.push((channel, key)),
+            MidiMessage::NoteOn { key, vel: _ } | MidiMessage::NoteOff { key, vel: _ } => {
+                self.sounding.retain(|note| *note != (channel, key))
+            }
+            _ => {}
+        }
+    }
+
+    /// sends a note off for every note that is still sounding.
+    fn release(&mut self, mut dispatch: impl FnMut(u8, MidiMessage)) {
+        self.sounding.drain(..).for_each(|(channel, key)| {
+            dispatch(
+                channel,
+                MidiMessage::NoteOff {
+                    key,
+                    vel: u7::from(0),
+                },
+            )
+        });
+    }
+}
+
+/// merges the tracks of `smf` into one list of channel messages timed in samples, following
+/// tempo changes. returns the events, the tempo changes and the length of the file in samples.
+fn schedule(smf: &Smf) -> (Vec<Event>, Vec<TempoChange>, u64) {
+    // (absolute tick, is not a tempo change, channel, message, tempo)
+    let mut ticked: Vec<(u64, bool, u8, Option<MidiMessage>, u32)> = Vec::new();
+    let mut end = 0;
+
+    for track in smf.tracks.iter() {
+        let mut tick = 0;
+
+        for event in track.iter() {
+            tick += event.delta.as_int() as u64;
+
+            match event.kind {
+                TrackEventKind::Midi { channel, message } => {
+                    ticked.push((tick, true, channel.as_int(), Some(message), 0))
+                }
+                TrackEventKind::Meta(MetaMessage::Tempo(tempo)) => {
+                    ticked.push((tick, false, 0, None, tempo.as_int()))
+                }
+                _ => {}
+            }
+        }
+
+        end = end.max(tick);
+    }
+
+    // stable, so events on the same tick keep their track order, tempo changes go first.
+    ticked.sort_by_key(|(tick, not_tempo, ..)| (*tick, *not_tempo));
+
+    let sample_rate = SAMPLE_RATE as f64;
+    // seconds per tick, as a function of the tempo for metrical timing.
+    let tick_len = |tempo: u32| match smf.header.timing {
+        Timing::Metrical(tpb) => tempo as f64 / 1_000_000.0 / tpb.as_int().max(1) as f64,
+        Timing::Timecode(fps, sub) => 1.0 / (fps.as_f32() as f64 * sub.max(1) as f64),
+    };
+
+    let mut tempo = DEFAULT_TEMPO;
+    let mut last_tick = 0;
+    let mut seconds = 0.0;
+    let mut events = Vec::with_capacity(ticked.len());
+    let mut tempos = Vec::new();
+
+    for (tick, _, channel, message, new_tempo) in ticked {
+        seconds += (tick - last_tick) as f64 * tick_len(tempo);
+        last_tick = tick;
+
+        let at = (seconds * sample_rate) as u64;
+
+        match message {
+            Some(message) => events.push((at, channel, message)),
+            None => {
+                tempo = new_tempo;
+                tempos.push((at, tempo));
+            }
+        }
+    }
+
+    seconds += end.saturating_sub(last_tick) as f64 * tick_len(tempo);
+
+    (events, tempos, (seconds * sample_rate) as u64)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use midly::{
+        Header, TrackEvent,
+        num::{u4, u24, u28},
+    };
+
+    fn event(delta: u32, kind: TrackEventKind<'static>) -> TrackEvent<'static> {
+        TrackEvent {
+            delta: u28::from(delta),
+            kind,
+        }
+    }
+
+    fn note(delta: u32, key: u8, on: bool) -> TrackEvent<'static> {
+        let (key, vel) = (u7::from(key), u7::from(if on { 100 } else { 0 }));
+        let message = if on {
+            MidiMessage::NoteOn { key, vel }
+        } else {
+            MidiMessage::NoteOff { key, vel }
+        };
+
+        event(
+            delta,
+            TrackEventKind::Midi {
+                channel: u4::from(0),
+                message,
+            },
+        )
+    }
+
+    fn tempo(delta: u32, tempo: u32) -> TrackEvent<'static> {
+        event(
+            delta,
+            TrackEventKind::Meta(MetaMessage::Tempo(u24::from(tempo))),
+        )
+    }
+
+    /// two beats of 480 ticks, the first at 120 BPM and the second at 60 BPM, with a note on each
+    /// beat. the notes are on a different track than the tempo changes.
+    fn smf() -> Smf<'static> {
+        let mut smf = Smf::new(Header::new(Format::Parallel, Timing::Metrical(480.into())));
+        smf.tracks.push(vec![
+            tempo(0, 500_000),
+            tempo(480, 1_000_000),
+            event(480, TrackEventKind::Meta(MetaMessage::EndOfTrack)),
+        ]);
+        smf.tracks.push(vec![
+            note(0, 60, true),
+            note(480, 60, false),
+            note(0, 62, true),
+            note(480, 62, false),
+        ]);
+
+        smf
+    }
+
+    fn seconds(s: Float) -> u64 {
+        (s * SAMPLE_RATE as Float) as u64
+    }
+
+    fn player() -> MidiPlayer {
+        let (events, tempos, length) = schedule(&smf());
+        let mut player = MidiPlayer {
+            events,
+            tempos,
+            length,
+            ..MidiPlayer::default()
+        };
+        player.rewind();
+
+        player
+    }
+
+    #[test]
+    fn schedule_follows_tempo_map() {
+        let (events, tempos, length) = schedule(&smf());
+        let at: Vec<u64> = events.iter().map(|(at, ..)| *at).collect();
+
+        assert_eq!(at, [0, seconds(0.5), seconds(0.5), seconds(1.5)]);
+        assert_eq!(tempos, [(0, 500_000), (seconds(0.5), 1_000_000)]);
+        assert_eq!(length, seconds(1.5));
+        // the note off comes before the next note on, like in the file
+        assert!(matches!(events[1].2, MidiMessage::NoteOff { .. }));
+    }
+
+    #[test]
+    fn step() {
+        let mut player = player();
+        let mut played = Vec::new();
+
+        assert_eq!(player.take_tempo_change(), Some(120.0));
+        assert_eq!(player.take_tempo_change(), None);
+
+        player.play();
+        (0..seconds(0.5)).for_each(|_| player.step(|_, message| played.push(message)));
+        assert_eq!(played.len(), 1);
+
+        _ = player.take_tempo_change();
+        player.step(|_, message| played.push(message));
+        assert_eq!(played.len(), 3);
+        assert_eq!(player.take_tempo_change(), Some(60.0));
+
+        (0..seconds(1.0) + 1).for_each(|_| player.step(|_, message| played.push(message)));
+        assert_eq!(played.len(), 4);
+        assert!(!player.is_playing());
+        assert_eq!(player.position(), 0.0);
+        assert_eq!(player.take_tempo_change(), Some(120.0));
+    }
+
+    #[test]
+    fn seek_releases_notes() {
+        let mut player = player();
+        let mut released = Vec::new();
+
+        player.play();
+        (0..seconds(0.25)).for_each(|_| player.step(|_, _| {}));
+        player.seek(1.0, |_, message| released.push(message));
+
+        assert_eq!(
+            released,
+            [MidiMessage::NoteOff {
+                key: u7::from(60),
+                vel: u7::from(0)
+            }]
+        );
+        assert_eq!(player.tempo(), 60.0);
+        assert_eq!(player.take_tempo_change(), Some(60.0));
+
+        player.seek(0.0, |_, _| {});
+        assert_eq!(player.tempo(), 120.0);
+    }
+}
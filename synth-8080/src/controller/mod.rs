@@ -1,18 +1,34 @@
 use crate::{
+    Float,
     common::notes::Note,
+    osc::wavetable::Wavetable,
     output::{self},
     router::Modules,
 };
 use anyhow::ensure;
+use clock::MidiClock;
 use crossbeam_channel::{Receiver, unbounded};
-use lib::{Connection, FilterType, ModuleType};
+use lib::{
+    Connection, FilterType, ModuleType,
+    mseg::Mseg,
+    tuning::{KeyboardMap, Scale, tuning},
+};
+use midi_file::MidiPlayer;
+use recorder::MidiRecorder;
 use rodio::{OutputStream, Source};
-use std::sync::Mutex;
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
 use tracing::*;
 
+pub mod clock;
+pub mod command;
 #[cfg(feature = "hardware")]
 pub mod hardware;
 pub mod midi;
+pub mod midi_file;
+pub mod recorder;
 
 pub struct Controller {
     /// the liist of connections
@@ -21,6 +37,12 @@ pub struct Controller {
     pub output: Mutex<output::Output>,
     pub sync: Receiver<()>,
     pub playing: Mutex<Vec<(usize, Note)>>,
+    /// plays MIDI files through the MCO. lock this before `modules` when both are needed.
+    pub player: Mutex<MidiPlayer>,
+    /// records incoming MIDI so it can be saved as a MIDI file.
+    pub recorder: Mutex<MidiRecorder>,
+    /// follows the tempo of an incoming MIDI clock.
+    pub clock: Mutex<MidiClock>,
 }
 
 impl Controller {
@@ -39,6 +61,9 @@ impl Controller {
                 sync,
                 output: Mutex::new(output),
                 playing: Mutex::new(Vec::new()),
+                player: Mutex::new(MidiPlayer::new()),
+                recorder: Mutex::new(MidiRecorder::new()),
+                clock: Mutex::new(MidiClock::new()),
             },
             jh,
         ))
@@ -130,6 +155,176 @@ impl Controller {
         playing.retain(|(_, n)| *n != note);
     }
 
+    /// loads a MIDI file to be played through the MCO.
+    pub fn load_midi_file(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let mut player = self.player.lock().unwrap();
+        let mco = &mut self.modules.lock().unwrap().mco[0];
+
+        player.load(path, |channel, message| {
+            midi::apply_message(mco, channel, message)
+        })
+    }
+
+    pub fn play_midi_file(&self) {
+        self.player.lock().unwrap().play();
+    }
+
+    pub fn stop_midi_file(&self) {
+        let mut player = self.player.lock().unwrap();
+        let mco = &mut self.modules.lock().unwrap().mco[0];
+
+        player.stop(|channel, message| midi::apply_message(mco, channel, message));
+    }
+
+    pub fn set_midi_file_loop(&self, looping: bool) {
+        self.player.lock().unwrap().set_loop(looping);
+    }
+
+    /// jumps to `seconds` into the loaded MIDI file.
+    pub fn seek_midi_file(&self, seconds: Float) {
+        let mut player = self.player.lock().unwrap();
+        let mco = &mut self.modules.lock().unwrap().mco[0];
+
+        player.seek(seconds, |channel, message| {
+            midi::apply_message(mco, channel, message)
+        });
+    }
+
+    /// advances MIDI file playback by one sample, called from the audio loop.
+    pub fn step_player(&self) {
+        let mut player = self.player.lock().unwrap();
+
+        if !player.is_playing() {
+            return;
+        }
+
+        {
+            let mco = &mut self.modules.lock().unwrap().mco[0];
+
+            player.step(|channel, message| midi::apply_message(mco, channel, message));
+        }
+
+        if let Some(tempo) = player.take_tempo_change() {
+            self.set_tempo(tempo);
+        }
+    }
+
+    /// starts recording incoming MIDI, anything recorded but not saved is discarded.
+    pub fn start_recording(&self) {
+        self.recorder.lock().unwrap().arm();
+    }
+
+    /// stops recording and saves the take to `path` as a MIDI file.
+    pub fn stop_recording(&self, path: impl AsRef<Path>) -> anyhow::Result<usize> {
+        self.recorder.lock().unwrap().stop(path)
+    }
+
+    /// sets the frequency of the reference note (A4 by default).
+    pub fn set_reference_pitch(&self, freq: Float) {
+        tuning().write().unwrap().set_reference(freq as f64);
+        self.retune();
+    }
+
+    /// tunes the synth to the scale in a Scala `.scl` file.
+    pub fn load_scale(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let scale = Scale::load(path)?;
+        info!("loaded scale: {}", scale.description);
+        tuning().write().unwrap().set_scale(scale);
+        self.retune();
+
+        Ok(())
+    }
+
+    /// maps the keyboard to the scale using a Scala `.kbm` file.
+    pub fn load_keyboard_map(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let map = KeyboardMap::load(path)?;
+        tuning().write().unwrap().set_map(map);
+        self.retune();
+
+        Ok(())
+    }
+
+    /// goes back to 12 tone equal temperament, keeping the reference pitch.
+    pub fn reset_tuning(&self) {
+        {
+            let mut tuning = tuning().write().unwrap();
+            let reference = tuning.map.reference_freq;
+
+            tuning.set_scale(Scale::default());
+            tuning.set_map(KeyboardMap {
+                reference_freq: reference,
+                ..Default::default()
+            });
+        }
+
+        self.retune();
+    }
+
+    /// re-applies the tuning to the notes being played.
+    fn retune(&self) {
+        let mut mods = self.modules.lock().unwrap();
+        mods.mco.iter_mut().for_each(|mco| mco.retune());
+    }
+
+    /// loads a wavetable (a single cycle or Serum style WAV file) for the MCO and VCOs.
+    pub fn load_wavetable(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let table = Arc::new(Wavetable::load(path)?);
+        let mut mods = self.modules.lock().unwrap();
+
+        mods.mco
+            .iter_mut()
+            .for_each(|mco| mco.set_wavetable(table.clone()));
+        mods.vco
+            .iter_mut()
+            .for_each(|vco| vco.osc.set_wavetable(table.clone()));
+
+        Ok(())
+    }
+
+    /// sets the breakpoints of the MSEG envelope of the MCO, every envelope filter and every
+    /// envelope module.
+    pub fn set_mseg(&self, mseg: Mseg) {
+        let mut mods = self.modules.lock().unwrap();
+
+        mods.mco.iter_mut().for_each(|mco| mco.set_mseg(&mseg));
+        mods.filter
+            .iter_mut()
+            .for_each(|filter| filter.set_mseg(mseg.clone()));
+        mods.env_gen
+            .iter_mut()
+            .for_each(|env| env.set_mseg(mseg.clone()));
+    }
+
+    /// sets the tempo in BPM that beat based MSEG times follow, for the MCO, every envelope filter
+    /// and every envelope module.
+    pub fn set_tempo(&self, tempo: Float) {
+        let mut mods = self.modules.lock().unwrap();
+
+        mods.mco.iter_mut().for_each(|mco| mco.set_tempo(tempo));
+        mods.filter
+            .iter_mut()
+            .for_each(|filter| filter.set_tempo(tempo));
+        mods.env_gen.iter_mut().for_each(|env| env.set_tempo(tempo));
+    }
+
+    /// loads an MSEG definition (see `lib::mseg`) from a file.
+    pub fn load_mseg(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        self.set_mseg(Mseg::load(path)?);
+
+        Ok(())
+    }
+
+    /// loads a wavetable for the LFO at index `id`.
+    pub fn load_lfo_wavetable(&self, id: usize, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let table = Arc::new(Wavetable::load(path)?);
+        let mut mods = self.modules.lock().unwrap();
+
+        ensure!(id < mods.lfo.len(), "there is no LFO {id}");
+        mods.lfo[id].osc.set_wavetable(table);
+
+        Ok(())
+    }
+
     /// sets filter type for all filters associated with a VCO
     pub fn set_filter_type(&self, filter_type: FilterType) {
         let mut mods = self.modules.lock().unwrap();
@@ -0,0 +1,122 @@
+use anyhow::{Result, bail};
+use midly::{
+    Format, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind,
+    live::LiveEvent,
+    num::{u4, u15, u24, u28},
+};
+use std::{path::Path, time::Instant};
+use tracing::*;
+
+/// ticks per beat of recorded MIDI files.
+pub const TICKS_PER_BEAT: u16 = 480;
+/// tempo of recorded MIDI files in micro seconds per beat (120 BPM).
+pub const RECORDING_TEMPO: u32 = 500_000;
+
+/// records the channel messages that come in through `MIDIControls` so they can be saved as a
+/// Standard MIDI File.
+#[derive(Debug, Default)]
+pub struct MidiRecorder {
+    /// when recording started, `None` when not recording.
+    armed_at: Option<Instant>,
+    /// per port offset (in micro seconds) that lines up that port's timestamps with `armed_at`.
+    /// midir timestamps are relative to when each connection was opened.
+    offsets: Vec<(String, i64)>,
+    /// (micro seconds since recording started, channel, message)
+    events: Vec<(u64, u8, MidiMessage)>,
+}
+
+impl MidiRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// starts a new recording, throwing away anything that was recorded but not saved.
+    pub fn arm(&mut self) {
+        self.armed_at = Some(Instant::now());
+        self.offsets.clear();
+        self.events.clear();
+        info!("recording midi");
+    }
+
+    pub fn is_armed(&self) -> bool {
+        self.armed_at.is_some()
+    }
+
+    /// records `message` which arrived from `port` at `stamp` micro seconds (as reported by midir).
+    pub fn record(&mut self, port: &str, stamp: u64, message: &[u8]) {
+        let Some(armed_at) = self.armed_at else {
+            return;
+        };
+
+        let Ok(LiveEvent::Midi { channel, message }) = LiveEvent::parse(message) else {
+            return;
+        };
+
+        let offset = match self.offsets.iter().find(|(name, _)| name == port) {
+            Some((_, offset)) => *offset,
+            None => {
+                let offset = armed_at.elapsed().as_micros() as i64 - stamp as i64;
+                self.offsets.push((port.to_string(), offset));
+                offset
+            }
+        };
+
+        let at = (stamp as i64 + offset).max(0) as u64;
+        self.events.push((at, channel.as_int(), message));
+    }
+
+    /// stops recording and writes what was recorded to `path`. returns the number of events
+    /// written. if the file can't be written it keeps recording, so the take isn't lost and
+    /// stopping can be tried again with another path.
+    pub fn stop(&mut self, path: impl AsRef<Path>) -> Result<usize> {
+        if self.armed_at.is_none() {
+            bail!("not recording");
+        }
+
+        let path = path.as_ref();
+        let events = &mut self.events;
+        // events from different ports can arrive slightly out of order.
+        events.sort_by_key(|(at, _, _)| *at);
+
+        let mut track = Vec::with_capacity(events.len() + 2);
+        track.push(TrackEvent {
+            delta: u28::from(0),
+            kind: TrackEventKind::Meta(MetaMessage::Tempo(u24::from(RECORDING_TEMPO))),
+        });
+
+        let mut last_tick = 0;
+
+        for (at, channel, message) in events.iter() {
+            let tick = at * TICKS_PER_BEAT as u64 / RECORDING_TEMPO as u64;
+
+            track.push(TrackEvent {
+                delta: u28::from((tick - last_tick) as u32),
+                kind: TrackEventKind::Midi {
+                    channel: u4::from(*channel),
+                    message: *message,
+                },
+            });
+            last_tick = tick;
+        }
+
+        track.push(TrackEvent {
+            delta: u28::from(0),
+            kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+        });
+
+        let mut smf = Smf::new(Header::new(
+            Format::SingleTrack,
+            Timing::Metrical(u15::from(TICKS_PER_BEAT)),
+        ));
+        smf.tracks.push(track);
+        smf.save(path)?;
+
+        let n_events = events.len();
+        info!("saved {n_events} midi events to {}", path.display());
+        self.armed_at = None;
+        self.offsets.clear();
+        self.events.clear();
+
+        Ok(n_events)
+    }
+}
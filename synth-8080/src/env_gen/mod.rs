@@ -0,0 +1,192 @@
+use crate::{
+    Float,
+    common::Module,
+    envelope::{Envelope, new_envelope},
+};
+use lib::{FilterType, RetriggerMode, mseg::Mseg};
+use tracing::*;
+
+pub const N_INPUTS: u8 = 8;
+pub const N_OUTPUTS: u8 = 3;
+
+pub const GATE_IN: u8 = 0;
+pub const RETRIGGER_IN: u8 = 1;
+pub const ENV_SELECT_IN: u8 = 2;
+/// the first of the envelope specific inputs (attack, decay, sus/break, decay 2, release), they
+/// match inputs 4..=8 of `envelope::EnvelopeFilter`.
+pub const ATTACK_IN: u8 = 3;
+
+pub const ENV_OUT: u8 = 0;
+pub const INVERTED_OUT: u8 = 1;
+/// goes high for one sample when the envelope finishes.
+pub const EOC_OUT: u8 = 2;
+
+/// an envelope on its own, to be used as a modulation source.
+pub struct EnvelopeGenerator {
+    pub filter_type: FilterType,
+    pub envelope: Box<dyn Envelope>,
+    /// the breakpoints used when the filter type is `FilterType::MSEG`
+    pub mseg: Mseg,
+    /// what the envelope does when it's gated again before finishing
+    pub retrigger_mode: RetriggerMode,
+    /// whether the retrigger input was high last sample, used to find rising and falling edges
+    retrigger_high: bool,
+    /// the gate input got samples since the last `get_samples`.
+    gate_seen: bool,
+    /// the gate input is patched, when it isn't the retrigger input doubles as the gate.
+    gate_driven: bool,
+    /// whether the envelope was running last sample, used for the end of cycle output
+    running: bool,
+}
+
+impl EnvelopeGenerator {
+    pub fn new() -> Self {
+        let mseg = Mseg::default();
+        let retrigger_mode = RetriggerMode::default();
+
+        Self {
+            filter_type: FilterType::ADSR,
+            envelope: new_envelope(FilterType::ADSR, &mseg, retrigger_mode),
+            mseg,
+            retrigger_mode,
+            retrigger_high: false,
+            gate_seen: false,
+            gate_driven: false,
+            running: false,
+        }
+    }
+
+    pub fn set_filter_type(&mut self, filter_type: FilterType) {
+        info!("setting envelope type to {filter_type:?}");
+        self.filter_type = filter_type;
+        self.envelope = new_envelope(filter_type, &self.mseg, self.retrigger_mode);
+    }
+
+    pub fn set_retrigger_mode(&mut self, mode: RetriggerMode) {
+        self.retrigger_mode = mode;
+        self.envelope.set_retrigger_mode(mode);
+    }
+
+    /// sets the breakpoints of the MSEG envelope, a running envelope carries on from its level.
+    pub fn set_mseg(&mut self, mseg: Mseg) {
+        self.mseg = mseg;
+        self.envelope.set_mseg(&self.mseg);
+    }
+
+    /// sets the tempo in BPM that the MSEG's beat based times follow.
+    pub fn set_tempo(&mut self, tempo: Float) {
+        self.mseg.tempo = tempo;
+        self.envelope.set_tempo(tempo);
+    }
+}
+
+impl Module for EnvelopeGenerator {
+    fn get_samples(&mut self) -> Vec<(u8, Float)> {
+        let env = self.envelope.step();
+        let running = self.envelope.pressed();
+        let eoc = if self.running && !running { 1.0 } else { 0.0 };
+        self.running = running;
+        // only connected inputs get samples, so this tells if the gate is patched.
+        self.gate_driven = std::mem::take(&mut self.gate_seen);
+
+        vec![(ENV_OUT, env), (INVERTED_OUT, 1.0 - env), (EOC_OUT, eoc)]
+    }
+
+    fn recv_samples(&mut self, input_n: u8, samples: &[Float]) {
+        if input_n == GATE_IN {
+            self.gate_seen = true;
+            self.envelope.open_filter(samples.to_vec());
+        } else if input_n == RETRIGGER_IN {
+            let high = samples.iter().sum::<Float>().tanh() >= 0.75;
+
+            if high && !self.retrigger_high {
+                self.envelope.retrigger();
+            } else if !high && self.retrigger_high && !self.gate_driven {
+                // used as a trigger on its own, let the envelope release.
+                self.envelope.open_filter(vec![0.0]);
+            }
+
+            self.retrigger_high = high;
+        } else if input_n == ENV_SELECT_IN {
+            let input = samples.iter().sum::<Float>();
+            let filter_type: FilterType = input.into();
+
+            if input >= 1.0 && filter_type != self.filter_type {
+                self.set_filter_type(filter_type);
+            }
+        } else if (ATTACK_IN..N_INPUTS).contains(&input_n) {
+            let _ = self
+                .envelope
+                .take_input(input_n - ATTACK_IN, samples.to_vec());
+        } else {
+            error!("invalid input: {input_n} for the Envelope module");
+        }
+    }
+
+    fn get_input_names() -> impl Iterator<Item = impl std::fmt::Display> {
+        [
+            "Gate",
+            "Retrigger",
+            "Env Select",
+            "Attack",
+            "Decay",
+            "Sus/Break",
+            "Decay2",
+            "Release",
+        ]
+        .iter()
+    }
+
+    fn get_output_names() -> impl Iterator<Item = impl std::fmt::Display> {
+        ["Env", "Inverted", "EOC"].iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::SAMPLE_RATE;
+
+    /// runs `samples` samples, feeding each `(input, value)` of `inputs`. returns the last envelope.
+    fn run(env: &mut EnvelopeGenerator, inputs: &[(u8, Float)], samples: usize) -> Float {
+        (0..samples).fold(0.0, |_, _| {
+            let out = env.get_samples()[ENV_OUT as usize].1;
+            inputs
+                .iter()
+                .for_each(|(input, value)| env.recv_samples(*input, &[*value]));
+
+            out
+        })
+    }
+
+    #[test]
+    fn trigger_only_releases() {
+        for filter_type in [FilterType::ADSR, FilterType::ADBDR, FilterType::MSEG] {
+            let mut env = EnvelopeGenerator::new();
+            env.set_filter_type(filter_type);
+
+            run(&mut env, &[(RETRIGGER_IN, 1.0)], 10);
+            assert!(run(&mut env, &[(RETRIGGER_IN, 1.0)], 1_000) > 0.0);
+
+            // well past any release
+            run(&mut env, &[(RETRIGGER_IN, 0.0)], 10 * SAMPLE_RATE as usize);
+            assert!(!env.envelope.pressed(), "{filter_type:?} never released");
+            assert!(run(&mut env, &[(RETRIGGER_IN, 0.0)], 1).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn retrigger_keeps_the_gate_open() {
+        let mut env = EnvelopeGenerator::new();
+
+        run(&mut env, &[(GATE_IN, 1.0), (RETRIGGER_IN, 1.0)], 10);
+        run(
+            &mut env,
+            &[(GATE_IN, 1.0), (RETRIGGER_IN, 0.0)],
+            10 * SAMPLE_RATE as usize,
+        );
+
+        assert!(env.envelope.pressed());
+        assert!(run(&mut env, &[(GATE_IN, 1.0)], 1) > 0.0);
+    }
+}
@@ -1,12 +1,13 @@
-use super::Envelope;
+use super::{Envelope, adsr::input_to_seconds};
 use crate::{Float, SAMPLE_RATE};
-use anyhow::{bail, Result};
+use anyhow::{Result, bail};
+use lib::RetriggerMode;
 
 pub const N_INPUTS: u8 = 4; // three for all filters, one for this filter
 pub const N_OUTPUTS: u8 = 1;
 
-pub const ATTACK_IN: u8 = 3; // sets attack speed in seconds
-pub const DECAY_IN: u8 = 4; // sets decay speed in seconds
+pub const ATTACK_IN: u8 = 4; // sets attack speed, 0 to 1.0 (see `adsr::input_to_seconds`)
+pub const DECAY_IN: u8 = 5; // sets decay speed, 0 to 1.0 (see `adsr::input_to_seconds`)
 
 #[derive(PartialEq, Eq, Debug, Clone, Copy)]
 enum Phase {
@@ -26,6 +27,7 @@ pub struct Filter {
     sample_rate: Float,
     pub decay: Float,
     pub pressed: bool,
+    pub retrigger_mode: RetriggerMode,
 }
 
 impl Filter {
@@ -34,7 +36,7 @@ impl Filter {
         let attack_speed = 0.5;
         let decay_speed = 0.5;
         let attack = 1.0 / (sample_rate * attack_speed);
-        let decay = -1.0 / (sample_rate * attack_speed);
+        let decay = 1.0 / (sample_rate * decay_speed);
 
         Self {
             // pressed: false,
@@ -47,6 +49,7 @@ impl Filter {
             decay_speed,
             decay,
             pressed: false,
+            retrigger_mode: RetriggerMode::default(),
         }
     }
 
@@ -109,28 +112,49 @@ impl Envelope for Filter {
             // info!("release");
             self.phase = Phase::Release;
             self.pressed = false;
-        } else if !self.pressed && self.phase == Phase::Neutural && sample >= 0.75 {
+        } else if !self.pressed && sample >= 0.75 {
             // info!("pressed");
-            self.phase = Phase::Attack;
-            self.pressed = true;
+            if self.phase == Phase::Neutural || self.retrigger_mode != RetriggerMode::Legato {
+                self.retrigger();
+            } else if self.phase == Phase::Release {
+                // legato, pick the decay back up from the current level
+                self.phase = Phase::Decay;
+                self.pressed = true;
+            } else {
+                self.pressed = true;
+            }
         }
 
         self.pressed
     }
 
     fn take_input(&mut self, input: u8, samples: Vec<Float>) -> Result<()> {
-        let sample: Float = samples.iter().sum::<Float>().tanh();
+        let sample: Float = samples.iter().sum::<Float>();
 
         match input {
             // attack in
-            ATTACK_IN => self.set_atk(sample),
-            DECAY_IN => self.set_decay(sample),
+            0 => self.set_atk(input_to_seconds(sample)),
+            // decay in
+            1 => self.set_decay(input_to_seconds(sample)),
             n => bail!("{n} is not a valid input for the AD filter."),
         }
 
         Ok(())
     }
 
+    fn retrigger(&mut self) {
+        if self.retrigger_mode == RetriggerMode::Reset {
+            self.env = 0.0;
+        }
+
+        self.phase = Phase::Attack;
+        self.pressed = true;
+    }
+
+    fn set_retrigger_mode(&mut self, mode: RetriggerMode) {
+        self.retrigger_mode = mode;
+    }
+
     fn pressed(&mut self) -> bool {
         self.phase != Phase::Neutural
     }
@@ -1,11 +1,12 @@
-use super::Envelope;
+use super::{Envelope, adsr::input_to_seconds};
 use crate::{Float, SAMPLE_RATE};
-use anyhow::{bail, Result};
+use anyhow::{Result, bail};
+use lib::RetriggerMode;
 
-pub const ATTACK_IN: u8 = 3; // sets attack speed in seconds
-pub const DECAY_1_IN: u8 = 4; // sets decay 1 speed in seconds
-pub const DECAY_THRESHOLD: u8 = 5; // sets the threshold between decay 1 & 2 in amplitude
-pub const DECAY_2_IN: u8 = 6; // sets decay 2 speed in seconds
+pub const ATTACK_IN: u8 = 4; // sets attack speed, 0 to 1.0 (see `adsr::input_to_seconds`)
+pub const DECAY_1_IN: u8 = 5; // sets decay 1 speed, 0 to 1.0 (see `adsr::input_to_seconds`)
+pub const DECAY_THRESHOLD: u8 = 6; // sets the threshold between decay 1 & 2 in amplitude
+pub const DECAY_2_IN: u8 = 7; // sets decay 2 speed, 0 to 1.0 (see `adsr::input_to_seconds`)
 
 #[derive(PartialEq, Eq, Debug, Clone, Copy)]
 enum Phase {
@@ -31,6 +32,7 @@ pub struct Filter {
     sample_rate: Float,
     pressed: bool,
     release_threshold: Float,
+    retrigger_mode: RetriggerMode,
 }
 
 impl Filter {
@@ -58,6 +60,7 @@ impl Filter {
             release: -0.9 / (sample_rate * 0.25),
             pressed: false,
             release_threshold: 0.05,
+            retrigger_mode: RetriggerMode::default(),
         }
     }
 
@@ -84,9 +87,7 @@ impl Filter {
     }
 
     fn set_threshold(&mut self, threshold: Float) {
-        if self.threshold != threshold {
-            self.threshold = threshold;
-        }
+        self.threshold = threshold.clamp(0.0, 1.0);
     }
 
     fn internal_update_phase(&mut self) {
@@ -136,39 +137,81 @@ impl Envelope for Filter {
         let sample: Float = samples.iter().sum::<Float>().tanh();
         // info!("envelope filter is open: {}", sample >= 0.75);
 
-        if self.pressed() && sample <= 0.75 {
+        if self.pressed && sample <= 0.75 {
             // info!("release");
             self.phase = Phase::Release;
+            self.release = -self.env / (self.sample_rate * 0.25);
             self.pressed = false;
-        } else if self.phase == Phase::Neutural && sample >= 0.75 {
+        } else if !self.pressed && sample >= 0.75 {
             // info!("pressed");
-            self.phase = Phase::Attack;
-            self.release = -self.env / (self.sample_rate * 0.01);
-            self.pressed = true;
+            if self.phase == Phase::Neutural || self.retrigger_mode != RetriggerMode::Legato {
+                self.retrigger();
+            } else {
+                // legato, go back to decaying from the current level
+                if self.phase == Phase::Release {
+                    self.phase = if self.env > self.threshold {
+                        Phase::Decay1
+                    } else {
+                        Phase::Decay2
+                    };
+                }
+
+                self.pressed = true;
+            }
         }
 
         self.pressed
     }
 
     fn take_input(&mut self, input: u8, samples: Vec<Float>) -> Result<()> {
-        let sample: Float = samples.iter().sum::<Float>().tanh();
+        let sample: Float = samples.iter().sum::<Float>();
 
         match input {
             // attack in
-            0 => self.set_attack(sample),
+            0 => self.set_attack(input_to_seconds(sample)),
             // decay_1 speed in
-            1 => self.set_decay_1(sample),
+            1 => self.set_decay_1(input_to_seconds(sample)),
             // decay_threshold in
             2 => self.set_threshold(sample),
             // decay_2 speed in
-            3 => self.set_decay_2(sample),
+            3 => self.set_decay_2(input_to_seconds(sample)),
             n => bail!("{n} is not a valid input for the ADBDR filter."),
         }
 
         Ok(())
     }
 
+    fn retrigger(&mut self) {
+        if self.retrigger_mode == RetriggerMode::Reset {
+            self.env = 0.0;
+        }
+
+        self.phase = Phase::Attack;
+        self.pressed = true;
+    }
+
+    fn set_retrigger_mode(&mut self, mode: RetriggerMode) {
+        self.retrigger_mode = mode;
+    }
+
     fn pressed(&mut self) -> bool {
         self.phase != Phase::Neutural
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn break_input_is_clamped() {
+        let mut env = Filter::new();
+
+        env.take_input(2, vec![0.6]).unwrap();
+        assert_eq!(env.threshold, 0.6);
+        env.take_input(2, vec![1.5]).unwrap();
+        assert_eq!(env.threshold, 1.0);
+        env.take_input(2, vec![-0.2]).unwrap();
+        assert_eq!(env.threshold, 0.0);
+    }
+}
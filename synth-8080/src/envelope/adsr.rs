@@ -1,15 +1,27 @@
-use super::Envelope;
+use super::{Envelope, shape};
 use crate::{Float, SAMPLE_RATE};
-use anyhow::{bail, Result};
+use anyhow::{Result, bail};
+use lib::{EnvCurve, EnvSegment, RetriggerMode};
 
 // use tracing::*;
 
 pub const N_INPUTS: u8 = 5;
 pub const N_OUTPUTS: u8 = 1;
 
-pub const ATTACK_IN: u8 = 3; // sets attack speed in seconds
-pub const DECAY_IN: u8 = 4; // sets decay 1 speed in seconds
-pub const DECAY_THRESHOLD: u8 = 5; // sets the threshold between decay 1 sustain in amplitude
+pub const ATTACK_IN: u8 = 4; // sets attack time
+pub const DECAY_IN: u8 = 5; // sets decay time
+pub const DECAY_THRESHOLD: u8 = 6; // sets the threshold between decay 1 sustain in amplitude
+pub const RELEASE_IN: u8 = 8; // sets release time
+
+/// shortest segment time in seconds.
+pub const MIN_TIME: Float = 0.001;
+/// longest segment time in seconds.
+pub const MAX_TIME: Float = 10.0;
+
+/// maps a 0..1 input exponentially onto `MIN_TIME..MAX_TIME` seconds, so 0.5 is 0.1 s.
+pub fn input_to_seconds(input: Float) -> Float {
+    MIN_TIME * (MAX_TIME / MIN_TIME).powf(input.clamp(0.0, 1.0))
+}
 
 #[derive(PartialEq, Eq, Debug, Clone, Copy)]
 enum Phase {
@@ -24,73 +36,93 @@ enum Phase {
 pub struct Filter {
     phase: Phase,
     env: Float,
-    pub decay_speed: Float,
-    pub attack_speed: Float,
+    /// attack time in seconds
+    pub attack_time: Float,
+    /// decay time in seconds
+    pub decay_time: Float,
+    /// release time in seconds
+    pub release_time: Float,
     pub threshold: Float,
-    pub decay: Float,
-    pub attack: Float,
-    pub release: Float,
+    pub attack_curve: EnvCurve,
+    pub decay_curve: EnvCurve,
+    pub release_curve: EnvCurve,
+    /// the level the current segment started from
+    start: Float,
+    /// the level the current segment is moving to
+    target: Float,
+    /// how far through the current segment the envelope is (0..1)
+    pos: Float,
     sample_rate: Float,
     pub pressed: bool,
-    release_threshold: Float,
+    pub retrigger_mode: RetriggerMode,
 }
 
 impl Filter {
     pub fn new() -> Self {
-        let sample_rate = SAMPLE_RATE as Float;
-        let attack_speed = 0.01;
-        let attack = 1.0 / (sample_rate * attack_speed);
-        let decay_speed = 0.1;
-        let decay = -1.0 / (sample_rate * decay_speed);
-
         Self {
             // pressed: false,
             phase: Phase::Neutural,
             // i: 0,
             env: 0.0,
-            decay,
-            attack,
+            attack_time: 0.01,
+            decay_time: 0.1,
+            release_time: 0.1,
             threshold: 0.9,
-            sample_rate,
-            attack_speed,
-            decay_speed,
-            release: -0.9 / (sample_rate * 0.1),
+            attack_curve: EnvCurve::Linear,
+            decay_curve: EnvCurve::Exponential,
+            release_curve: EnvCurve::Exponential,
+            start: 0.0,
+            target: 0.0,
+            pos: 0.0,
+            sample_rate: SAMPLE_RATE as Float,
             pressed: false,
-            release_threshold: 0.05,
+            retrigger_mode: RetriggerMode::default(),
         }
     }
 
-    fn set_attack(&mut self, atk_speed: Float) {
-        if atk_speed != self.attack_speed {
-            self.attack_speed = atk_speed;
-            self.attack = 1.0 / (self.sample_rate * atk_speed);
-        }
+    fn set_attack(&mut self, attack: Float) {
+        self.attack_time = input_to_seconds(attack);
     }
 
-    fn set_decay(&mut self, decay_speed: Float) {
-        if decay_speed != self.decay_speed {
-            self.decay_speed = decay_speed;
-            self.decay = -1.0 / (self.sample_rate * decay_speed);
-        }
+    fn set_decay(&mut self, decay: Float) {
+        self.decay_time = input_to_seconds(decay);
+    }
+
+    fn set_release(&mut self, release: Float) {
+        self.release_time = input_to_seconds(release);
     }
 
     fn set_threshold(&mut self, threshold: Float) {
-        self.threshold = threshold;
-        self.release = -threshold / (self.sample_rate * 0.01);
+        self.threshold = threshold.clamp(0.0, 1.0);
+    }
+
+    /// starts a new segment that moves from the current level to `target`.
+    fn start_segment(&mut self, phase: Phase, target: Float) {
+        self.phase = phase;
+        self.start = self.env;
+        self.target = target;
+        self.pos = 0.0;
+    }
+
+    /// the time and curve of the current segment.
+    fn segment(&self) -> Option<(Float, EnvCurve)> {
+        match self.phase {
+            Phase::Attack => Some((self.attack_time, self.attack_curve)),
+            Phase::Decay => Some((self.decay_time, self.decay_curve)),
+            Phase::Release => Some((self.release_time, self.release_curve)),
+            Phase::Sustain | Phase::Neutural => None,
+        }
     }
 
     fn internal_update_phase(&mut self) {
-        if self.phase == Phase::Attack && self.env >= 1.0 {
-            self.phase = Phase::Decay;
+        if self.phase == Phase::Attack && self.pos >= 1.0 {
             self.env = 1.0;
+            self.start_segment(Phase::Decay, self.threshold);
             // info!("changing phase to => {:?}", self.phase);
-        } else if self.phase == Phase::Decay && self.env <= self.threshold {
+        } else if self.phase == Phase::Decay && self.pos >= 1.0 {
             self.phase = Phase::Sustain;
             // info!("changing phase to => {:?}", self.phase);
-            // } else if self.phase == Phase::Sustain && self.env <= self.release_threshold {
-            //     self.phase = Phase::Release;
-            // info!("changing phase to => {:?}", self.phase);
-        } else if self.phase == Phase::Release && self.env <= 0.0 {
+        } else if self.phase == Phase::Release && self.pos >= 1.0 {
             self.phase = Phase::Neutural;
             self.env = 0.0;
             // info!("changing phase to => {:?}", self.phase);
@@ -108,13 +140,18 @@ impl Envelope for Filter {
     }
 
     fn get_step(&mut self) -> Float {
-        match self.phase {
-            Phase::Attack => self.attack,
-            Phase::Decay => self.decay,
-            Phase::Sustain => 0.0,
-            Phase::Release => self.release,
-            Phase::Neutural => 0.0,
+        if self.phase == Phase::Sustain {
+            return self.threshold - self.env;
         }
+
+        let Some((time, curve)) = self.segment() else {
+            return 0.0;
+        };
+
+        self.pos = (self.pos + 1.0 / (self.sample_rate * time)).min(1.0);
+        let level = self.start + (self.target - self.start) * shape(curve, self.pos);
+
+        level - self.env
     }
 
     fn update_phase(&mut self) {
@@ -126,15 +163,20 @@ impl Envelope for Filter {
 
         if self.pressed && sample <= 0.75 {
             // info!("release");
-            self.phase = Phase::Release;
+            self.start_segment(Phase::Release, 0.0);
             self.pressed = false;
-        } else if !self.pressed
-            && (self.phase == Phase::Neutural || self.phase == Phase::Release)
-            && sample >= 0.75
-        {
+        } else if !self.pressed && sample >= 0.75 {
             // info!("pressed");
-            self.phase = Phase::Attack;
             self.pressed = true;
+
+            if self.phase == Phase::Neutural {
+                self.start_segment(Phase::Attack, 1.0);
+            } else {
+                match self.retrigger_mode {
+                    RetriggerMode::Reset | RetriggerMode::Restart => self.retrigger(),
+                    RetriggerMode::Legato => self.start_segment(Phase::Decay, self.threshold),
+                }
+            }
         }
 
         self.pressed
@@ -150,12 +192,35 @@ impl Envelope for Filter {
             1 => self.set_decay(sample),
             // decay_threshold in
             2 => self.set_threshold(sample),
+            // release speed in
+            4 => self.set_release(sample),
             n => bail!("{n} is not a valid input for the ADSR filter."),
         }
 
         Ok(())
     }
 
+    fn retrigger(&mut self) {
+        if self.retrigger_mode == RetriggerMode::Reset {
+            self.env = 0.0;
+        }
+
+        self.pressed = true;
+        self.start_segment(Phase::Attack, 1.0);
+    }
+
+    fn set_retrigger_mode(&mut self, mode: RetriggerMode) {
+        self.retrigger_mode = mode;
+    }
+
+    fn set_curve(&mut self, segment: EnvSegment, curve: EnvCurve) {
+        match segment {
+            EnvSegment::Attack => self.attack_curve = curve,
+            EnvSegment::Decay => self.decay_curve = curve,
+            EnvSegment::Release => self.release_curve = curve,
+        }
+    }
+
     fn pressed(&mut self) -> bool {
         self.phase != Phase::Neutural
         // self.pressed
@@ -45,8 +45,9 @@ impl AllPassFilter {
         self.a1 = (tan - 1.0) / (tan + 1.0)
     }
 
+    /// multiplies the base cutoff by `wiggle_amount`.
     pub fn wiggle_cutoff(&mut self, wiggle_amount: Float) {
-        self.cutoff = self.base_cutoff + (5_000.0 * wiggle_amount) + 100.0;
+        self.cutoff = (self.base_cutoff * wiggle_amount).clamp(20.0, SAMPLE_RATE as Float * 0.45);
         // trace!(
         //     "base_cutoff: {} | cutoff: {}, | wiggle: {}",
         //     self.base_cutoff,
@@ -77,7 +78,7 @@ impl Filter for AllPassFilter {
     fn take_env(&mut self, env: Float) {
         self.env = env;
         // self.wiggle_resonance(env);
-        // self.wiggle_cutoff(env * 0.25);
+        self.wiggle_cutoff(env);
     }
 
     fn get_sample(&mut self, audio_in: Float) -> Float {
@@ -228,7 +229,7 @@ impl LowPassFilter {
     }
 
     pub fn wiggle_cutoff(&mut self, wiggle_amount: Float) {
-        self.cutoff = self.base_cutoff * wiggle_amount;
+        self.cutoff = (self.base_cutoff * wiggle_amount).clamp(20.0, SAMPLE_RATE as Float * 0.45);
         // trace!(
         //     "base_cutoff: {} | cutoff: {}, | wiggle: {}",
         //     self.base_cutoff,
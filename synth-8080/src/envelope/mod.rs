@@ -1,26 +1,51 @@
-use crate::{common::Module, Float};
+use crate::{Float, common::Module};
 use anyhow::Result;
-use lib::FilterType;
+use lib::{EnvCurve, EnvSegment, FilterMode, FilterSlope, FilterType, RetriggerMode, mseg::Mseg};
 use log::info;
 use std::f32::NAN;
 use tracing::*;
 
-use self::allpass::{AllPassFilter, LowPassFilter};
+use self::{
+    allpass::{AllPassFilter, LowPassFilter},
+    svf::Svf,
+};
 
 pub mod ad;
 pub mod adbdr;
 pub mod adsr;
 pub mod allpass;
+pub mod mseg;
 pub mod none;
 pub mod oc;
+pub mod svf;
 
-pub const N_INPUTS: u8 = 8;
+pub const N_INPUTS: u8 = 9;
 pub const N_OUTPUTS: u8 = 3;
 
 pub const FILTER_SELECT_IN: u8 = 0;
 pub const AUDIO_IN: u8 = 1;
 pub const FILTER_OPEN_IN: u8 = 2;
 pub const AUDIO_OUT: u8 = 0;
+pub const ENV_OUT: u8 = 1;
+
+/// how many octaves the cutoff drops for the softest note at full velocity amount.
+pub const VELOCITY_OCTAVES: Float = 3.0;
+/// the MIDI note key tracking is relative to (middle C).
+pub const KEY_TRACKING_CENTER: u8 = 60;
+/// how many octaves the MPE timbre moves the cutoff across its whole range.
+pub const TIMBRE_OCTAVES: Float = 4.0;
+
+/// how bent the exponential and logarithmic curves are.
+const CURVATURE: Float = 5.0;
+
+/// returns how far along (0..1) a segment is at position `t` (0..1) for the given curve.
+pub fn shape(curve: EnvCurve, t: Float) -> Float {
+    match curve {
+        EnvCurve::Linear => t,
+        EnvCurve::Exponential => (1.0 - (-CURVATURE * t).exp()) / (1.0 - (-CURVATURE).exp()),
+        EnvCurve::Logarithmic => ((CURVATURE * t).exp() - 1.0) / (CURVATURE.exp() - 1.0),
+    }
+}
 
 pub trait Envelope: Send {
     fn step(&mut self) -> Float {
@@ -55,14 +80,68 @@ pub trait Envelope: Send {
     /// pressed.
     fn open_filter(&mut self, samples: Vec<Float>) -> bool;
 
+    /// restarts the envelope from its current level as if the gate was just opened.
+    fn retrigger(&mut self) {
+        self.open_filter(vec![0.0]);
+        self.open_filter(vec![1.0]);
+    }
+
+    /// sets what happens when the gate opens before the envelope has finished, envelopes without
+    /// an attack ignore this.
+    fn set_retrigger_mode(&mut self, _mode: RetriggerMode) {}
+
+    /// sets the curve of one segment, envelopes with only linear segments ignore this.
+    fn set_curve(&mut self, _segment: EnvSegment, _curve: EnvCurve) {}
+
+    /// sets the tempo in BPM that beat based times follow, only used by the MSEG.
+    fn set_tempo(&mut self, _tempo: Float) {}
+
+    /// swaps in new breakpoints without restarting the envelope, only used by the MSEG.
+    fn set_mseg(&mut self, _mseg: &Mseg) {}
+
     /// returns true if the filter is not in its neuteral state.
     fn pressed(&mut self) -> bool;
 }
 
+/// makes a fresh envelope of type `filter_type`, `mseg` is only used by `FilterType::MSEG`.
+pub fn new_envelope(
+    filter_type: FilterType,
+    mseg: &Mseg,
+    retrigger_mode: RetriggerMode,
+) -> Box<dyn Envelope> {
+    let mut envelope: Box<dyn Envelope> = match filter_type {
+        FilterType::None => Box::new(none::Filter::new()),
+        FilterType::ADSR => Box::new(adsr::Filter::new()),
+        FilterType::ADBDR => Box::new(adbdr::Filter::new()),
+        FilterType::OC => Box::new(oc::Filter::new()),
+        FilterType::AD => Box::new(ad::Filter::new()),
+        FilterType::MSEG => Box::new(mseg::Filter::new(mseg.clone())),
+    };
+    envelope.set_retrigger_mode(retrigger_mode);
+
+    envelope
+}
+
+/// makes the filter for `mode`, `slope` is only used by the state-variable modes.
+pub fn new_filter(mode: FilterMode, slope: FilterSlope) -> Box<dyn Filter> {
+    if mode != FilterMode::Moog {
+        Box::new(Svf::new(mode, slope))
+    } else if cfg!(feature = "allpass") {
+        warn!("using allpass-based lowpass filter to save reasource");
+        let mut filter = AllPassFilter::new();
+        filter.init();
+        Box::new(filter)
+    } else {
+        warn!("using true lowpass filter to for better quality");
+        Box::new(LowPassFilter::new())
+    }
+}
+
 pub trait Filter: Send {
     fn init(&mut self);
 
-    /// takes an env value from envelope and updates internal values acourdingly.
+    /// takes a cutoff multiplier (from the filter envelope, key tracking and velocity) and updates
+    /// internal values acourdingly.
     fn take_env(&mut self, env: Float);
 
     /// returns generated sample.
@@ -89,20 +168,41 @@ pub struct EnvelopeFilter {
     // pub allpass: AllPassFilter,
     // pub lowpass: LowPassFilter,
     pub filter: Box<dyn Filter>,
+    /// a copy of `filter` for the side (stereo difference) signal of a voice, see `filter_side`
+    side_filter: Box<dyn Filter>,
+    /// the envelope level of the last sample
+    level: Float,
+    pub filter_mode: FilterMode,
+    pub filter_slope: FilterSlope,
+    /// the last cutoff and resonance set, kept so they survive a change of filter mode
+    cutoff: Option<Float>,
+    resonance: Option<Float>,
+    /// the breakpoints used when the filter type is `FilterType::MSEG`
+    pub mseg: Mseg,
+    /// what the envelope does when it's gated again before finishing
+    pub retrigger_mode: RetriggerMode,
+    /// an ADSR envelope that only moves the cutoff, gated with `envelope`
+    pub filter_env: adsr::Filter,
+    /// the cutoff follows `envelope` (like it did before there was a filter envelope) until one of
+    /// the filter envelope's inputs is set.
+    pub filter_env_linked: bool,
+    /// how far the filter envelope moves the cutoff, -1.0 to 1.0. at 1.0 the cutoff is scaled by
+    /// the envelope, at 0 it stays put and below 0 it's scaled by the inverted envelope.
+    pub filter_env_amount: Float,
+    /// how much the cutoff follows the note, 1.0 moves it an octave per octave
+    pub key_tracking: Float,
+    /// how much softer notes close the filter, 0 to 1.0
+    pub velocity_amount: Float,
+    /// the MIDI note being played, used for key tracking
+    pub note: u8,
+    /// the velocity of the note being played, 0 to 1.0
+    pub velocity: Float,
+    /// per note cutoff offset in octaves from MPE timbre, reset when a note starts
+    pub timbre: Float,
 }
 
 impl EnvelopeFilter {
     pub fn new(id: u8) -> Self {
-        let filter: Box<dyn Filter> = if cfg!(feature = "allpass") {
-            warn!("using allpass-based lowpass filter to save reasource");
-            let mut filter = AllPassFilter::new();
-            filter.init();
-            Box::new(filter)
-        } else {
-            warn!("using true lowpass filter to for better quality");
-            Box::new(LowPassFilter::new())
-        };
-
         Self {
             filter_type: FilterType::ADSR,
             pressed: false,
@@ -111,20 +211,147 @@ impl EnvelopeFilter {
             id,
             // allpass: filter,
             // lowpass: LowPassFilter::new(),
-            filter,
+            filter: new_filter(FilterMode::default(), FilterSlope::default()),
+            side_filter: new_filter(FilterMode::default(), FilterSlope::default()),
+            level: 0.0,
+            filter_mode: FilterMode::default(),
+            filter_slope: FilterSlope::default(),
+            cutoff: None,
+            resonance: None,
+            mseg: Mseg::default(),
+            retrigger_mode: RetriggerMode::default(),
+            filter_env: adsr::Filter::new(),
+            filter_env_linked: true,
+            filter_env_amount: 1.0,
+            key_tracking: 0.0,
+            velocity_amount: 0.0,
+            note: KEY_TRACKING_CENTER,
+            velocity: 1.0,
+            timbre: 0.0,
         }
     }
 
+    /// sets the note and velocity used for key tracking and velocity to cutoff, call before
+    /// opening the filter.
+    pub fn set_note(&mut self, note: u8, velocity: Float) {
+        self.note = note;
+        self.velocity = velocity.clamp(0.0, 1.0);
+        self.timbre = 0.0;
+    }
+
+    /// moves the cutoff of the note being played, `timbre` is between 0 and 1.0 with 0.5 leaving
+    /// it alone.
+    pub fn set_timbre(&mut self, timbre: Float) {
+        self.timbre = (timbre.clamp(0.0, 1.0) - 0.5) * TIMBRE_OCTAVES;
+    }
+
+    /// sends samples to an input of the filter envelope, inputs are numbered like
+    /// `Envelope::take_input` of `adsr::Filter`.
+    pub fn set_filter_env(&mut self, input: u8, value: Float) {
+        self.filter_env_linked = false;
+
+        if let Err(e) = self.filter_env.take_input(input, vec![value]) {
+            error!("{e}");
+        }
+    }
+
+    /// the cutoff multiplier from the filter envelope, key tracking, velocity and timbre. `env` is
+    /// the value of `envelope`, used while the filter envelope is linked to it.
+    fn cutoff_mod(&mut self, env: Float) -> Float {
+        let filter_env = self.filter_env.step();
+        let env = if self.filter_env_linked {
+            env
+        } else {
+            filter_env
+        };
+        let amount = self.filter_env_amount;
+        let env = if amount >= 0.0 {
+            1.0 - amount * (1.0 - env)
+        } else {
+            1.0 + amount * env
+        };
+        let key = (self.note as Float - KEY_TRACKING_CENTER as Float) / 12.0 * self.key_tracking;
+        let velocity = (self.velocity - 1.0) * self.velocity_amount * VELOCITY_OCTAVES;
+
+        env * (key + velocity + self.timbre).exp2()
+    }
+
     pub fn set_filter_type(&mut self, filter_type: FilterType) {
         self.filter_type = filter_type;
         info!("setting filter type to {:?}", self.filter_type);
-        self.envelope = match self.filter_type {
-            // FilterType::None => Box::new(none::Filter::new()),
-            FilterType::ADSR => Box::new(adsr::Filter::new()),
-            // FilterType::ADBDR => Box::new(adbdr::Filter::new()),
-            // FilterType::OC => Box::new(oc::Filter::new()),
-            // FilterType::AD => Box::new(ad::Filter::new()),
+        self.envelope = new_envelope(self.filter_type, &self.mseg, self.retrigger_mode);
+    }
+
+    pub fn set_retrigger_mode(&mut self, mode: RetriggerMode) {
+        self.retrigger_mode = mode;
+        self.envelope.set_retrigger_mode(mode);
+        self.filter_env.set_retrigger_mode(mode);
+    }
+
+    /// sets the breakpoints of the MSEG envelope, a running envelope carries on from its level.
+    pub fn set_mseg(&mut self, mseg: Mseg) {
+        self.mseg = mseg;
+        self.envelope.set_mseg(&self.mseg);
+    }
+
+    /// sets the tempo in BPM that the MSEG's beat based times follow.
+    pub fn set_tempo(&mut self, tempo: Float) {
+        self.mseg.tempo = tempo;
+        self.envelope.set_tempo(tempo);
+    }
+
+    /// swaps the filter for one of `mode` and `slope`, keeping the cutoff and resonance.
+    pub fn set_filter_mode(&mut self, mode: FilterMode, slope: FilterSlope) {
+        if mode == self.filter_mode && slope == self.filter_slope {
+            return;
+        }
+
+        info!("setting filter mode to {mode:?} ({slope:?})");
+        self.filter_mode = mode;
+        self.filter_slope = slope;
+        self.filter = new_filter(mode, slope);
+        self.side_filter = new_filter(mode, slope);
+
+        if let Some(cutoff) = self.cutoff {
+            self.filter.set_cutoff(cutoff);
+            self.side_filter.set_cutoff(cutoff);
+        }
+
+        if let Some(resonance) = self.resonance {
+            self.filter.set_resonance(resonance);
+            self.side_filter.set_resonance(resonance);
+        }
+    }
+
+    /// takes a number between 0 and 1.0
+    pub fn set_cutoff(&mut self, cutoff: Float) {
+        self.cutoff = Some(cutoff);
+        self.filter.set_cutoff(cutoff);
+        self.side_filter.set_cutoff(cutoff);
+    }
+
+    /// takes a number between 0 and 1.0
+    pub fn set_resonance(&mut self, resonance: Float) {
+        self.resonance = Some(resonance);
+        self.filter.set_resonance(resonance);
+        self.side_filter.set_resonance(resonance);
+    }
+
+    /// filters the side (left - right) signal of a stereo voice the same way as the audio input,
+    /// call it after `get_samples` so it follows the same envelopes.
+    pub fn filter_side(&mut self, side: Float) -> Float {
+        let filtered = self.side_filter.get_sample(side);
+        let sample = if self.filter_mode == FilterMode::Moog {
+            side + filtered
+        } else {
+            filtered
         };
+
+        sample * self.level
+    }
+
+    pub fn set_curve(&mut self, segment: EnvSegment, curve: EnvCurve) {
+        self.envelope.set_curve(segment, curve);
     }
 
     pub fn is_pressed(&mut self) -> bool {
@@ -135,8 +362,17 @@ impl EnvelopeFilter {
 impl Module for EnvelopeFilter {
     fn get_samples(&mut self) -> Vec<(u8, Float)> {
         let env = self.envelope.step();
-        self.filter.take_env(env);
-        let sample = (self.audio_in + self.filter.get_sample(self.audio_in)) * env;
+        let cutoff_mod = self.cutoff_mod(env);
+        self.filter.take_env(cutoff_mod);
+        self.side_filter.take_env(cutoff_mod);
+        self.level = env;
+        let filtered = self.filter.get_sample(self.audio_in);
+        // the moog filter has always been mixed with the dry signal, that would undo a highpass.
+        let sample = if self.filter_mode == FilterMode::Moog {
+            self.audio_in + filtered
+        } else {
+            filtered
+        } * env;
         // let sample = self.audio_in * env;
 
         let open = if self.envelope.pressed() { 1.0 } else { 0.0 };
@@ -148,10 +384,12 @@ impl Module for EnvelopeFilter {
         // TODO: add lowpass filter controls
         if input_n == FILTER_SELECT_IN {
             // self.filter_select_in_cons.lock().unwrap().push(connection);
-            let input = samples.iter().sum::<Float>().tanh();
-            if input > 1.0 {
-                // let mut ft = ft.lock().unwrap();
-                self.set_filter_type(input.into());
+            // the raw sum is used here, tanh would never reach the 1.0..7.0 select range
+            let input = samples.iter().sum::<Float>();
+            let filter_type: FilterType = input.into();
+
+            if input >= 1.0 && filter_type != self.filter_type {
+                self.set_filter_type(filter_type);
             }
         } else if input_n == AUDIO_IN {
             let audio = samples.iter().sum::<Float>().tanh();
@@ -159,12 +397,14 @@ impl Module for EnvelopeFilter {
         } else if input_n == FILTER_OPEN_IN {
             // let input: Float = samples.iter().sum();
             self.pressed = self.envelope.open_filter(samples.to_vec());
+            self.filter_env.open_filter(samples.to_vec());
             // info!("pressed => {}", self.pressed);
         } else if input_n == 3 {
+            // moves the resonance around the value last set with `set_resonance`
             let sample: Float = samples.iter().sum();
-            // self.allpass.set_cutoff(sample.tanh());
-            // self.allpass.wiggle_cutoff(sample.tanh());
-            // self.filter.
+            let resonance = (self.resonance.unwrap_or(0.5) + sample.tanh() * 0.5).clamp(0.0, 1.0);
+            self.filter.set_resonance(resonance);
+            self.side_filter.set_resonance(resonance);
         } else if input_n == 4 {
             let _ = self.envelope.take_input(0, samples.to_vec());
         } else if input_n == 5 {
@@ -173,6 +413,8 @@ impl Module for EnvelopeFilter {
             let _ = self.envelope.take_input(2, samples.to_vec());
         } else if input_n == 7 {
             let _ = self.envelope.take_input(3, samples.to_vec());
+        } else if input_n == 8 {
+            let _ = self.envelope.take_input(4, samples.to_vec());
         } else {
             error!("invalid input selection {:?}:{input_n}", self.filter_type);
         }
@@ -188,6 +430,7 @@ impl Module for EnvelopeFilter {
             "Decay",
             "Sus/Break",
             "Decay2",
+            "Release",
         ]
         .iter()
     }
@@ -196,3 +439,72 @@ impl Module for EnvelopeFilter {
         ["Audio Out", "Env", "Open"].iter()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn close(a: Float, b: Float) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn gates_share_a_threshold() {
+        [FilterType::None, FilterType::OC, FilterType::ADSR]
+            .into_iter()
+            .for_each(|filter_type| {
+                let mut env = new_envelope(filter_type, &Mseg::default(), RetriggerMode::default());
+
+                // tanh(0.9) is just under the threshold, tanh(1.0) just over.
+                assert!(!env.open_filter(vec![0.9]), "{filter_type:?}");
+                assert!(env.open_filter(vec![1.0]), "{filter_type:?}");
+            });
+    }
+
+    #[test]
+    fn cutoff_follows_the_envelope_by_default() {
+        let mut filter = EnvelopeFilter::new(0);
+
+        assert!(close(filter.cutoff_mod(0.5), 0.5));
+        assert!(close(filter.cutoff_mod(1.0), 1.0));
+    }
+
+    #[test]
+    fn cutoff_mod_amount() {
+        let mut filter = EnvelopeFilter::new(0);
+
+        filter.filter_env_amount = 0.5;
+        assert!(close(filter.cutoff_mod(0.0), 0.5));
+        filter.filter_env_amount = 0.0;
+        assert!(close(filter.cutoff_mod(0.0), 1.0));
+        filter.filter_env_amount = -1.0;
+        assert!(close(filter.cutoff_mod(0.25), 0.75));
+    }
+
+    #[test]
+    fn cutoff_mod_key_tracking_and_velocity() {
+        let mut filter = EnvelopeFilter::new(0);
+        filter.key_tracking = 1.0;
+        filter.set_note(KEY_TRACKING_CENTER + 12, 1.0);
+        assert!(close(filter.cutoff_mod(1.0), 2.0));
+
+        filter.key_tracking = 0.5;
+        assert!(close(filter.cutoff_mod(1.0), 2.0_f32.sqrt()));
+
+        filter.key_tracking = 0.0;
+        filter.velocity_amount = 1.0;
+        filter.set_note(KEY_TRACKING_CENTER, 0.0);
+        assert!(close(filter.cutoff_mod(1.0), (-VELOCITY_OCTAVES).exp2()));
+        filter.set_note(KEY_TRACKING_CENTER, 1.0);
+        assert!(close(filter.cutoff_mod(1.0), 1.0));
+    }
+
+    #[test]
+    fn own_filter_env_once_set() {
+        let mut filter = EnvelopeFilter::new(0);
+        filter.set_filter_env(0, 0.0);
+
+        // the filter envelope hasn't been gated, so it's closed whatever `envelope` does.
+        assert!(close(filter.cutoff_mod(1.0), 0.0));
+    }
+}
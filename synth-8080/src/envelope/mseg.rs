@@ -0,0 +1,190 @@
+use super::{Envelope, adsr::MIN_TIME, shape};
+use crate::{Float, SAMPLE_RATE};
+use anyhow::{Result, bail};
+use lib::{RetriggerMode, mseg::Mseg};
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum Phase {
+    Neutural,
+    /// moving through a segment
+    Running,
+    /// holding at the sustain point
+    Sustain,
+}
+
+#[derive(Debug, Clone)]
+pub struct Filter {
+    phase: Phase,
+    env: Float,
+    /// the breakpoints
+    mseg: Mseg,
+    /// the length of every segment in seconds
+    times: Vec<Float>,
+    /// the segment currently playing
+    segment: usize,
+    /// the level the current segment started from
+    start: Float,
+    /// how far through the current segment the envelope is (0..1)
+    pos: Float,
+    sample_rate: Float,
+    pressed: bool,
+    retrigger_mode: RetriggerMode,
+}
+
+impl Filter {
+    pub fn new(mseg: Mseg) -> Self {
+        let mut filter = Self {
+            phase: Phase::Neutural,
+            env: 0.0,
+            mseg,
+            times: Vec::new(),
+            segment: 0,
+            start: 0.0,
+            pos: 0.0,
+            sample_rate: SAMPLE_RATE as Float,
+            pressed: false,
+            retrigger_mode: RetriggerMode::default(),
+        };
+        filter.set_tempo(filter.mseg.tempo);
+
+        filter
+    }
+
+    /// starts segment `i` from the current level, stops the envelope if there are no segments left.
+    fn start_segment(&mut self, i: usize) {
+        if i < self.mseg.points.len() {
+            self.phase = Phase::Running;
+            self.segment = i;
+            self.start = self.env;
+            self.pos = 0.0;
+        } else {
+            self.phase = Phase::Neutural;
+        }
+    }
+
+    fn internal_update_phase(&mut self) {
+        if self.phase != Phase::Running || self.pos < 1.0 {
+            return;
+        }
+
+        let i = self.segment;
+        self.env = self.mseg.points[i].level;
+        let loop_start = self
+            .mseg
+            .loop_region
+            .and_then(|(start, end)| (end == i).then_some(start));
+
+        if self.pressed && self.mseg.sustain == Some(i) {
+            self.phase = Phase::Sustain;
+        } else if let Some(start) = loop_start.filter(|_| self.pressed) {
+            self.start_segment(start);
+        } else {
+            self.start_segment(i + 1);
+        }
+    }
+}
+
+impl Envelope for Filter {
+    fn get_env(&mut self) -> Float {
+        self.env
+    }
+
+    fn set_env(&mut self, env: Float) {
+        self.env = env;
+    }
+
+    fn get_step(&mut self) -> Float {
+        if self.phase != Phase::Running {
+            return 0.0;
+        }
+
+        let point = self.mseg.points[self.segment];
+
+        self.pos = (self.pos + 1.0 / (self.sample_rate * self.times[self.segment])).min(1.0);
+        let level = self.start + (point.level - self.start) * shape(point.curve, self.pos);
+
+        level - self.env
+    }
+
+    fn update_phase(&mut self) {
+        self.internal_update_phase()
+    }
+
+    fn open_filter(&mut self, samples: Vec<Float>) -> bool {
+        let sample: Float = samples.iter().sum::<Float>().tanh();
+
+        if self.pressed && sample <= 0.75 {
+            self.pressed = false;
+            let release = self.mseg.release_start();
+
+            // one-shot envelopes (no sustain or loop) just keep going.
+            if self.phase == Phase::Sustain || (self.segment < release && release > 0) {
+                self.start_segment(release);
+            }
+        } else if !self.pressed && sample >= 0.75 {
+            if self.phase == Phase::Neutural || self.retrigger_mode != RetriggerMode::Legato {
+                self.retrigger();
+            } else {
+                // legato, head back to the sustain point (or loop) from the current level
+                self.pressed = true;
+                let hold = self
+                    .mseg
+                    .sustain
+                    .or(self.mseg.loop_region.map(|(start, _)| start));
+
+                if let Some(i) = hold.filter(|i| self.segment > *i) {
+                    self.start_segment(i);
+                }
+            }
+        }
+
+        self.pressed
+    }
+
+    fn retrigger(&mut self) {
+        if self.retrigger_mode == RetriggerMode::Reset {
+            self.env = 0.0;
+        }
+
+        self.pressed = true;
+        self.start_segment(0);
+    }
+
+    fn set_retrigger_mode(&mut self, mode: RetriggerMode) {
+        self.retrigger_mode = mode;
+    }
+
+    fn take_input(&mut self, input: u8, _samples: Vec<Float>) -> Result<()> {
+        bail!("{input} is not a valid input for the MSEG filter.")
+    }
+
+    fn pressed(&mut self) -> bool {
+        self.phase != Phase::Neutural
+    }
+
+    fn set_tempo(&mut self, tempo: Float) {
+        if tempo <= 0.0 {
+            return;
+        }
+
+        self.mseg.tempo = tempo;
+        self.times.clear();
+        self.times.extend(
+            self.mseg
+                .points
+                .iter()
+                .map(|point| point.time.seconds(tempo).max(MIN_TIME)),
+        );
+    }
+
+    fn set_mseg(&mut self, mseg: &Mseg) {
+        self.mseg.clone_from(mseg);
+        self.set_tempo(mseg.tempo);
+
+        // glide from the current level into the new shape instead of jumping.
+        if self.phase != Phase::Neutural {
+            let last = self.mseg.points.len().saturating_sub(1);
+            self.start_segment(self.segment.min(last));
+        }
+    }
+}
@@ -1,11 +1,13 @@
 use super::Envelope;
 use crate::Float;
 
-pub struct Filter {}
+pub struct Filter {
+    open: bool,
+}
 
 impl Filter {
     pub fn new() -> Self {
-        Self {}
+        Self { open: false }
     }
 }
 
@@ -27,12 +29,12 @@ impl Envelope for Filter {
     }
 
     fn open_filter(&mut self, samples: Vec<crate::Float>) -> bool {
-        let input = samples.iter().sum::<Float>();
+        self.open = samples.iter().sum::<Float>().tanh() >= 0.75;
 
-        input >= 0.75
+        self.open
     }
 
     fn pressed(&mut self) -> bool {
-        false
+        self.open
     }
 }
@@ -34,6 +34,6 @@ impl Envelope for Filter {
     }
 
     fn pressed(&mut self) -> bool {
-        false
+        self.open
     }
 }
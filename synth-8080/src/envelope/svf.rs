@@ -0,0 +1,160 @@
+use super::Filter;
+use lib::{FilterMode, FilterSlope, Float, SAMPLE_RATE};
+use std::f32::consts::{PI, SQRT_2};
+
+/// lowest cutoff in Hz.
+pub const MIN_CUTOFF: Float = 20.0;
+/// highest cutoff in Hz, kept under nyquist so the coefficients stay stable.
+pub const MAX_CUTOFF: Float = 18_000.0;
+/// damping of the first stage of the 24 dB slope. Butterworth, so only the second stage resonates
+/// and the peak stays at the resonance of one stage.
+const BUTTERWORTH_K: Float = SQRT_2;
+
+/// one 2-pole stage, holds the state of the two integrators.
+#[derive(Debug, Clone, Copy, Default)]
+struct Stage {
+    ic1eq: Float,
+    ic2eq: Float,
+}
+
+/// the coefficients of one stage for a given cutoff and damping.
+#[derive(Debug, Clone, Copy, Default)]
+struct Coefs {
+    k: Float,
+    a1: Float,
+    a2: Float,
+    a3: Float,
+}
+
+impl Coefs {
+    fn new(g: Float, k: Float) -> Self {
+        let a1 = 1.0 / (1.0 + g * (g + k));
+        let a2 = g * a1;
+
+        Self {
+            k,
+            a1,
+            a2,
+            a3: g * a2,
+        }
+    }
+}
+
+/// a multimode state-variable filter (Andrew Simper's trapezoidal integrated SVF).
+#[derive(Debug, Clone)]
+pub struct Svf {
+    mode: FilterMode,
+    slope: FilterSlope,
+    /// the cutoff in Hz that the envelope sweeps up to.
+    base_cutoff: Float,
+    cutoff: Float,
+    /// damping, 2 is no resonance, 0 self oscillates.
+    k: Float,
+    /// coefficients with the resonance applied.
+    coefs: Coefs,
+    /// coefficients of the non resonant first stage of the 24 dB slope.
+    flat: Coefs,
+    stages: [Stage; 2],
+}
+
+impl Svf {
+    pub fn new(mode: FilterMode, slope: FilterSlope) -> Self {
+        let cutoff = 3_000.0;
+        let mut filter = Self {
+            mode,
+            slope,
+            base_cutoff: cutoff,
+            cutoff,
+            k: 1.0,
+            coefs: Coefs::default(),
+            flat: Coefs::default(),
+            stages: [Stage::default(); 2],
+        };
+        filter.recalculate();
+
+        filter
+    }
+
+    fn recalculate(&mut self) {
+        let cutoff = self.cutoff.clamp(MIN_CUTOFF, MAX_CUTOFF);
+        let g = (PI * cutoff / SAMPLE_RATE as Float).tan();
+
+        self.coefs = Coefs::new(g, self.k);
+        self.flat = Coefs::new(g, BUTTERWORTH_K);
+    }
+
+    /// sets the cutoff in Hz directly, bypassing the envelope.
+    pub fn set_cutoff_hz(&mut self, cutoff: Float) {
+        self.base_cutoff = cutoff;
+        self.cutoff = cutoff;
+        self.recalculate();
+    }
+
+    /// sets the resonance as a Q factor directly, the bandpass output peaks at `q`.
+    pub fn set_q(&mut self, q: Float) {
+        self.k = 1.0 / q.max(0.5);
+        self.recalculate();
+    }
+
+    /// runs one stage, returns the (lowpass, bandpass, highpass) outputs.
+    pub fn process(&mut self, stage: usize, v0: Float) -> (Float, Float, Float) {
+        self.run(stage, v0, self.coefs)
+    }
+
+    fn run(&mut self, stage: usize, v0: Float, c: Coefs) -> (Float, Float, Float) {
+        let s = &mut self.stages[stage];
+
+        let v3 = v0 - s.ic2eq;
+        let v1 = c.a1 * s.ic1eq + c.a2 * v3;
+        let v2 = s.ic2eq + c.a2 * s.ic1eq + c.a3 * v3;
+        s.ic1eq = 2.0 * v1 - s.ic1eq;
+        s.ic2eq = 2.0 * v2 - s.ic2eq;
+
+        (v2, v1, v0 - c.k * v1 - v2)
+    }
+
+    /// runs one stage with coefficients `c`, returns the output for the current mode.
+    fn tick(&mut self, stage: usize, v0: Float, c: Coefs) -> Float {
+        let (low, band, high) = self.run(stage, v0, c);
+
+        match self.mode {
+            FilterMode::LowPass | FilterMode::Moog => low,
+            FilterMode::HighPass => high,
+            FilterMode::BandPass => band,
+            FilterMode::Notch => low + high,
+        }
+    }
+}
+
+impl Filter for Svf {
+    fn init(&mut self) {}
+
+    fn take_env(&mut self, env: Float) {
+        self.cutoff = self.base_cutoff * env;
+        self.recalculate();
+    }
+
+    fn get_sample(&mut self, audio_in: Float) -> Float {
+        match self.slope {
+            FilterSlope::Db12 => self.tick(0, audio_in, self.coefs),
+            FilterSlope::Db24 => {
+                let sample = self.tick(0, audio_in, self.flat);
+                self.tick(1, sample, self.coefs)
+            }
+        }
+    }
+
+    /// takes a number between 0 and 1.0, mapped exponentially from `MIN_CUTOFF` to `MAX_CUTOFF`.
+    fn set_cutoff(&mut self, cutoff: Float) {
+        self.base_cutoff = MIN_CUTOFF * (MAX_CUTOFF / MIN_CUTOFF).powf(cutoff.clamp(0.0, 1.0));
+        self.cutoff = self.base_cutoff;
+        self.recalculate();
+    }
+
+    /// takes a number between 0 and 1.0.
+    fn set_resonance(&mut self, resonance: Float) {
+        // stop just short of self oscillation
+        self.k = 2.0 - 1.95 * resonance.clamp(0.0, 1.0);
+        self.recalculate();
+    }
+}
@@ -0,0 +1,76 @@
+use crate::{
+    Float,
+    common::Module,
+    envelope::{Filter, svf::Svf},
+};
+use lib::{FilterMode, FilterSlope};
+use tracing::*;
+
+pub const N_INPUTS: u8 = 3;
+pub const N_OUTPUTS: u8 = 3;
+
+pub const AUDIO_IN: u8 = 0;
+pub const CUTOFF_IN: u8 = 1;
+pub const RESONANCE_IN: u8 = 2;
+
+pub const LOW_PASS_OUT: u8 = 0;
+pub const HIGH_PASS_OUT: u8 = 1;
+pub const BAND_PASS_OUT: u8 = 2;
+
+/// the cutoff in Hz with no cutoff CV.
+pub const BASE_CUTOFF: Float = 1_000.0;
+/// how many octaves one unit of cutoff CV moves the cutoff, so -1.0..1.0 covers the audible range.
+pub const OCTAVES_PER_UNIT: Float = 5.0;
+
+/// a state-variable filter on its own, with lowpass, highpass and bandpass outputs.
+pub struct FilterModule {
+    filter: Svf,
+    audio_in: Float,
+}
+
+impl FilterModule {
+    pub fn new() -> Self {
+        let mut filter = Svf::new(FilterMode::LowPass, FilterSlope::Db12);
+        filter.set_cutoff_hz(BASE_CUTOFF);
+
+        Self {
+            filter,
+            audio_in: 0.0,
+        }
+    }
+}
+
+impl Module for FilterModule {
+    fn get_samples(&mut self) -> Vec<(u8, Float)> {
+        let (low, band, high) = self.filter.process(0, self.audio_in);
+
+        vec![
+            (LOW_PASS_OUT, low),
+            (HIGH_PASS_OUT, high),
+            (BAND_PASS_OUT, band),
+        ]
+    }
+
+    fn recv_samples(&mut self, input_n: u8, samples: &[Float]) {
+        let sample: Float = samples.iter().sum();
+
+        if input_n == AUDIO_IN {
+            self.audio_in = sample.tanh();
+        } else if input_n == CUTOFF_IN {
+            self.filter
+                .set_cutoff_hz(BASE_CUTOFF * (sample * OCTAVES_PER_UNIT).exp2());
+        } else if input_n == RESONANCE_IN {
+            self.filter.set_resonance(sample);
+        } else {
+            error!("invalid input: {input_n} for the Filter module");
+        }
+    }
+
+    fn get_input_names() -> impl Iterator<Item = impl std::fmt::Display> {
+        ["Audio In", "Cutoff", "Resonance"].iter()
+    }
+
+    fn get_output_names() -> impl Iterator<Item = impl std::fmt::Display> {
+        ["Low Pass", "High Pass", "Band Pass"].iter()
+    }
+}
@@ -0,0 +1,112 @@
+use crate::{Float, SAMPLE_RATE, common::Module, envelope::adsr::input_to_seconds};
+use tracing::*;
+
+pub const N_INPUTS: u8 = 4;
+pub const N_OUTPUTS: u8 = 3;
+
+pub const AUDIO_IN: u8 = 0;
+/// 0 to 1.0, mapped the same way as the envelope times.
+pub const ATTACK_IN: u8 = 1;
+/// 0 to 1.0, mapped the same way as the envelope times.
+pub const RELEASE_IN: u8 = 2;
+/// the level the envelope has to reach to open the gate, 0 to 1.0.
+pub const THRESHOLD_IN: u8 = 3;
+
+pub const ENV_OUT: u8 = 0;
+pub const INVERTED_OUT: u8 = 1;
+/// 1.0 while the envelope is over the threshold.
+pub const GATE_OUT: u8 = 2;
+
+/// the gate closes once the envelope falls under this fraction of the threshold, so a signal
+/// hovering around the threshold doesn't chatter.
+pub const HYSTERESIS: Float = 0.5;
+
+/// turns a time in seconds into a one-pole smoothing coefficient.
+fn coefficient(seconds: Float) -> Float {
+    (-1.0 / (seconds * SAMPLE_RATE as Float)).exp()
+}
+
+/// tracks the amplitude of an audio input and outputs it as a control signal.
+pub struct EnvelopeFollower {
+    env: Float,
+    attack: Float,
+    release: Float,
+    /// the CV the attack and release coefficients were last calculated from
+    attack_in: Float,
+    release_in: Float,
+    threshold: Float,
+    gate: bool,
+    audio_in: Float,
+}
+
+impl EnvelopeFollower {
+    pub fn new() -> Self {
+        let attack_in = 0.2;
+        let release_in = 0.5;
+
+        Self {
+            env: 0.0,
+            attack: coefficient(input_to_seconds(attack_in)),
+            release: coefficient(input_to_seconds(release_in)),
+            attack_in,
+            release_in,
+            threshold: 0.1,
+            gate: false,
+            audio_in: 0.0,
+        }
+    }
+}
+
+impl Module for EnvelopeFollower {
+    fn get_samples(&mut self) -> Vec<(u8, Float)> {
+        let level = self.audio_in.abs();
+        let coef = if level > self.env {
+            self.attack
+        } else {
+            self.release
+        };
+        self.env = level + (self.env - level) * coef;
+
+        if !self.gate && self.env >= self.threshold {
+            self.gate = true;
+        } else if self.gate && self.env < self.threshold * HYSTERESIS {
+            self.gate = false;
+        }
+
+        vec![
+            (ENV_OUT, self.env),
+            (INVERTED_OUT, 1.0 - self.env),
+            (GATE_OUT, if self.gate { 1.0 } else { 0.0 }),
+        ]
+    }
+
+    fn recv_samples(&mut self, input_n: u8, samples: &[Float]) {
+        let sample: Float = samples.iter().sum();
+
+        if input_n == AUDIO_IN {
+            self.audio_in = sample.tanh();
+        } else if input_n == ATTACK_IN {
+            if sample != self.attack_in {
+                self.attack_in = sample;
+                self.attack = coefficient(input_to_seconds(sample));
+            }
+        } else if input_n == RELEASE_IN {
+            if sample != self.release_in {
+                self.release_in = sample;
+                self.release = coefficient(input_to_seconds(sample));
+            }
+        } else if input_n == THRESHOLD_IN {
+            self.threshold = sample.clamp(0.001, 1.0);
+        } else {
+            error!("invalid input: {input_n} for the Follower module");
+        }
+    }
+
+    fn get_input_names() -> impl Iterator<Item = impl std::fmt::Display> {
+        ["Audio In", "Attack", "Release", "Threshold"].iter()
+    }
+
+    fn get_output_names() -> impl Iterator<Item = impl std::fmt::Display> {
+        ["Env", "Inverted", "Gate"].iter()
+    }
+}
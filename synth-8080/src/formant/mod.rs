@@ -0,0 +1,123 @@
+use crate::{Float, common::Module, envelope::svf::Svf};
+use lib::{FilterMode, FilterSlope};
+use tracing::*;
+
+pub const N_INPUTS: u8 = 2;
+pub const N_OUTPUTS: u8 = 1;
+
+pub const AUDIO_IN: u8 = 0;
+/// 0 to 1.0 glides through the vowels A, E, I, O, U.
+pub const MORPH_IN: u8 = 1;
+
+pub const AUDIO_OUT: u8 = 0;
+
+/// the first three formants of each vowel (bass voice) as (frequency in Hz, gain in dB, bandwidth
+/// in Hz), in the order A, E, I, O, U.
+const VOWELS: [[(Float, Float, Float); 3]; 5] = [
+    [
+        (600.0, 0.0, 60.0),
+        (1040.0, -7.0, 70.0),
+        (2250.0, -9.0, 110.0),
+    ],
+    [
+        (400.0, 0.0, 40.0),
+        (1620.0, -12.0, 80.0),
+        (2400.0, -9.0, 100.0),
+    ],
+    [
+        (250.0, 0.0, 60.0),
+        (1750.0, -30.0, 90.0),
+        (2600.0, -16.0, 100.0),
+    ],
+    [
+        (400.0, 0.0, 40.0),
+        (750.0, -11.0, 80.0),
+        (2400.0, -21.0, 100.0),
+    ],
+    [
+        (350.0, 0.0, 40.0),
+        (600.0, -20.0, 80.0),
+        (2400.0, -32.0, 100.0),
+    ],
+];
+
+/// a bank of parallel bandpass filters tuned to the formants of a vowel.
+pub struct FormantFilter {
+    formants: [Svf; 3],
+    /// linear gain of each formant, divided by its Q so the peaks don't grow with narrow bands
+    gains: [Float; 3],
+    morph: Float,
+    audio_in: Float,
+}
+
+impl FormantFilter {
+    pub fn new() -> Self {
+        let mut filter = Self {
+            formants: std::array::from_fn(|_| Svf::new(FilterMode::BandPass, FilterSlope::Db12)),
+            gains: [1.0; 3],
+            morph: 0.0,
+            audio_in: 0.0,
+        };
+        filter.set_morph(0.0);
+
+        filter
+    }
+
+    /// moves between the vowels, 0 is A and 1.0 is U.
+    pub fn set_morph(&mut self, morph: Float) {
+        self.morph = morph.clamp(0.0, 1.0);
+
+        let pos = self.morph * (VOWELS.len() - 1) as Float;
+        let i = (pos as usize).min(VOWELS.len() - 2);
+        let t = pos - i as Float;
+        let lerp = |a: Float, b: Float| a + (b - a) * t;
+
+        for (n, formant) in self.formants.iter_mut().enumerate() {
+            let (freq_a, gain_a, bw_a) = VOWELS[i][n];
+            let (freq_b, gain_b, bw_b) = VOWELS[i + 1][n];
+            let freq = lerp(freq_a, freq_b);
+
+            let q = freq / lerp(bw_a, bw_b);
+
+            formant.set_cutoff_hz(freq);
+            formant.set_q(q);
+            self.gains[n] = (10.0 as Float).powf(lerp(gain_a, gain_b) / 20.0) / q.max(0.5);
+        }
+    }
+}
+
+impl Module for FormantFilter {
+    fn get_samples(&mut self) -> Vec<(u8, Float)> {
+        let audio_in = self.audio_in;
+        let sample: Float = self
+            .formants
+            .iter_mut()
+            .zip(self.gains)
+            .map(|(formant, gain)| formant.process(0, audio_in).1 * gain)
+            .sum();
+
+        vec![(AUDIO_OUT, sample)]
+    }
+
+    fn recv_samples(&mut self, input_n: u8, samples: &[Float]) {
+        let sample: Float = samples.iter().sum();
+
+        if input_n == AUDIO_IN {
+            self.audio_in = sample.tanh();
+        } else if input_n == MORPH_IN {
+            if (sample - self.morph).abs() > Float::EPSILON {
+                self.set_morph(sample);
+            }
+        } else {
+            error!("invalid input: {input_n} for the Formant module");
+        }
+    }
+
+    fn get_input_names() -> impl Iterator<Item = impl std::fmt::Display> {
+        ["Audio In", "Morph"].iter()
+    }
+
+    fn get_output_names() -> impl Iterator<Item = impl std::fmt::Display> {
+        ["Audio Out"].iter()
+    }
+}
@@ -1,16 +1,17 @@
 use crate::{
+    Float,
     common::Module,
     osc::{OscType, Oscillator},
-    Float,
 };
 use tracing::*;
 
-pub const N_INPUTS: u8 = 3;
+pub const N_INPUTS: u8 = 4;
 pub const N_OUTPUTS: u8 = 2;
 
 pub const PITCH_IN: u8 = 0;
 pub const VOL_IN: u8 = 1;
 pub const OSC_TYPE_IN: u8 = 2;
+pub const POSITION_IN: u8 = 3;
 
 pub struct Lfo {
     // pub routing_table: Router,
@@ -19,6 +20,8 @@ pub struct Lfo {
     pub osc: Oscillator,
     /// where the data from the volume input is stored
     pub volume_in: Float,
+    /// wavetable position set from the controls
+    pub position: Float,
     pub id: u8,
 }
 
@@ -36,6 +39,7 @@ impl Lfo {
             osc_type,
             osc,
             volume_in,
+            position: 0.0,
             id,
         }
     }
@@ -51,6 +55,12 @@ impl Lfo {
     pub fn set_pitch(&mut self, pitch: Float) {
         self.osc.set_frequency(pitch);
     }
+
+    /// sets the wavetable position (0.0 to 1.0), the position input is added on top of this.
+    pub fn set_position(&mut self, position: Float) {
+        self.position = position;
+        self.osc.set_position(position);
+    }
 }
 
 impl Module for Lfo {
@@ -68,13 +78,16 @@ impl Module for Lfo {
             self.volume_in = (samples.iter().sum::<Float>().tanh() + 1.0) * 0.5;
         } else if input_n == OSC_TYPE_IN {
             error!("can not yet set LFO oscillator type via input");
+        } else if input_n == POSITION_IN {
+            self.osc
+                .set_position(self.position + samples.iter().sum::<Float>());
         } else {
             error!("invalid input: {input_n} for LFO module");
         }
     }
 
     fn get_input_names() -> impl Iterator<Item = impl std::fmt::Display> {
-        ["Pitch", "Vol."].iter()
+        ["Pitch", "Vol.", "Osc Type", "Position"].iter()
     }
 
     fn get_output_names() -> impl Iterator<Item = impl std::fmt::Display> {
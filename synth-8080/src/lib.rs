@@ -11,17 +11,25 @@ use tracing::*;
 
 pub type JoinHandle = tokio::task::JoinHandle<()>;
 
+/// the most inputs or outputs a module can have.
+pub const MAX_PORTS: usize = 32;
+
 pub mod audio_in;
 pub mod chorus;
 pub mod common;
 pub mod controller;
 pub mod delay;
 pub mod echo;
+pub mod env_gen;
 pub mod envelope;
+pub mod filter;
+pub mod follower;
+pub mod formant;
 pub mod gain;
 pub mod lfo;
 pub mod mid_pass;
 pub mod midi_osc;
+pub mod noise;
 pub mod osc;
 pub mod output;
 pub mod overdrive;
@@ -45,7 +53,9 @@ impl Future for AudioGen {
             error!("error receiving sync message: {e}");
         };
 
-        let mut src_samples = [[0.0; 16]; u8::MAX as usize];
+        self.controller.step_player();
+
+        let mut src_samples = [[0.0; MAX_PORTS]; u8::MAX as usize];
 
         // info!("locking controller.modules");
 
@@ -59,7 +69,7 @@ impl Future for AudioGen {
             }
         }
 
-        let mut dest_samples = [[0.0; 16]; u8::MAX as usize];
+        let mut dest_samples = [[0.0; MAX_PORTS]; u8::MAX as usize];
         let mut destinations: Vec<(u8, u8)> = Vec::with_capacity(256);
 
         // info!("locking controller.connections");
@@ -94,6 +104,8 @@ impl Future for AudioGen {
             }
         }
 
+        self.controller.output.lock().unwrap().send_frame();
+
         // info!("sent samples");
 
         cx.waker().wake_by_ref();
@@ -132,6 +144,12 @@ pub fn default_modules() -> Vec<ModuleType> {
         ModuleType::Lfo,
         ModuleType::Lfo,
         ModuleType::Lfo,
+        // added after the originals so their module IDs don't move
+        ModuleType::Noise,
+        ModuleType::Envelope,
+        ModuleType::Filter,
+        ModuleType::Formant,
+        ModuleType::Follower,
     ]
     .to_vec()
 }
@@ -1,15 +1,25 @@
 use crate::{
     common::Module,
     envelope::{self, EnvelopeFilter, FILTER_OPEN_IN, Filter, adbdr, adsr},
+    osc::{Spectrum, wavetable::Wavetable},
     vco::{self, PITCH_BEND_INPUT, Vco},
 };
 use anyhow::{Result, bail};
-use lib::{FilterType, Float, OscType, notes::Note};
-use std::ops::IndexMut;
+use lib::{
+    EnvCurve, EnvSegment, FilterMode, FilterSlope, FilterType, Float, OscType, RetriggerMode,
+    SubWaveform, SyncMode, mseg::Mseg, notes::Note,
+};
+use mpe::Mpe;
+use std::{f32::consts::FRAC_1_SQRT_2, ops::IndexMut, sync::Arc};
 use tracing::*;
 
+pub mod mpe;
+
 pub const N_INPUTS: u8 = envelope::N_INPUTS + vco::N_INPUTS;
-pub const N_OUTPUTS: u8 = 1;
+pub const N_OUTPUTS: u8 = 3;
+pub const AUDIO_OUT: u8 = 0;
+pub const LEFT_OUT: u8 = 1;
+pub const RIGHT_OUT: u8 = 2;
 // pub const PITCH_BEND: u8 = 9;
 // pub const VOLUME: u8 = 8;
 
@@ -18,7 +28,11 @@ pub struct MidiOsc {
     /// how many vcos/envs there are
     size: usize,
     notes: Vec<Option<Note>>,
+    /// the MIDI channel each voice's note was played on.
+    channels: Vec<u8>,
     pub overtones: bool,
+    /// MPE zones, when on, notes on member channels get their own bend, pressure and timbre.
+    pub mpe: Mpe,
     // TODO: add a single vco/env combo to be controlled with signal inputs
 }
 
@@ -42,7 +56,9 @@ impl MidiOsc {
             oscs: oscs.collect(),
             size,
             notes: notes.collect(),
+            channels: vec![0; size],
             overtones: false,
+            mpe: Mpe::default(),
         }
     }
 
@@ -53,6 +69,7 @@ impl MidiOsc {
             .collect();
 
         self.notes = (0..n).into_iter().map(|_| None).collect();
+        self.channels = vec![0; n];
         self.size = n;
     }
 
@@ -68,8 +85,27 @@ impl MidiOsc {
     }
 
     pub fn play_note(&mut self, note: Note) -> Result<()> {
+        self.play_note_on(0, note)
+    }
+
+    /// plays `note` received on MIDI `channel`. with MPE on, notes on member channels start with
+    /// that channel's pitch bend and can be played on several channels at once.
+    pub fn play_note_on(&mut self, channel: u8, note: Note) -> Result<()> {
+        self.play_note_with_velocity(channel, note, 1.0)
+    }
+
+    /// like `play_note_on`, `velocity` is between 0 and 1.0 and can close the filter (see
+    /// `set_velocity_amount`).
+    pub fn play_note_with_velocity(
+        &mut self,
+        channel: u8,
+        note: Note,
+        velocity: Float,
+    ) -> Result<()> {
         // pub fn play_note(&mut self, note: Float) -> Result<()> {
-        if self.notes.contains(&Some(note)) {
+        let member = self.mpe.is_member(channel);
+
+        if self.voice_of(channel, note).is_some() {
             bail!("{note} is already being played.");
         }
         // else {
@@ -79,10 +115,23 @@ impl MidiOsc {
         for i in 0..self.size {
             if self.notes[i].is_none() {
                 self.notes[i] = Some(note);
+                self.channels[i] = channel;
 
                 let (vco, env) = self.oscs.index_mut(i);
 
                 vco.set_note(note);
+                env.set_note(note, velocity);
+
+                // member channels start from the expression sent ahead of the note.
+                if member {
+                    vco.pressure = self.mpe.pressure(channel);
+                    vco.osc.set_note_bend(self.mpe.bend_semitones(channel));
+                    env.set_timbre(self.mpe.timbre(channel));
+                } else {
+                    vco.pressure = 1.0;
+                    vco.osc.set_note_bend(0.0);
+                }
+
                 env.recv_samples(FILTER_OPEN_IN, &vec![1.0]);
 
                 return Ok(());
@@ -93,24 +142,98 @@ impl MidiOsc {
     }
 
     pub fn stop_note(&mut self, note: Note) -> Result<()> {
-        if !self.notes.contains(&Some(note)) {
+        self.stop_note_on(0, note)
+    }
+
+    /// stops `note` received on MIDI `channel`.
+    pub fn stop_note_on(&mut self, channel: u8, note: Note) -> Result<()> {
+        let Some(i) = self.voice_of(channel, note) else {
             bail!("{note} is not being played.");
-        }
+        };
 
-        for i in 0..self.size {
-            if self.notes[i] == Some(note) {
-                self.notes[i] = None;
+        self.notes[i] = None;
 
-                let (vco, env) = self.oscs.index_mut(i);
+        let (_vco, env) = self.oscs.index_mut(i);
 
-                // vco.osc.set_frequency(0.0);
-                env.recv_samples(FILTER_OPEN_IN, &vec![0.0]);
+        // vco.osc.set_frequency(0.0);
+        env.recv_samples(FILTER_OPEN_IN, &vec![0.0]);
 
-                return Ok(());
-            }
-        }
+        Ok(())
+    }
 
-        bail!("note not found, or an unknown error ocured...");
+    /// finds the voice playing `note`, only notes played on `channel` are considered if it is an
+    /// MPE member channel.
+    fn voice_of(&self, channel: u8, note: Note) -> Option<usize> {
+        let member = self.mpe.is_member(channel);
+
+        (0..self.size)
+            .find(|i| self.notes[*i] == Some(note) && (!member || self.channels[*i] == channel))
+    }
+
+    /// returns the voices playing notes on MIDI `channel`.
+    fn voices_on(&mut self, channel: u8) -> impl Iterator<Item = &mut (Vco, EnvelopeFilter)> {
+        self.oscs
+            .iter_mut()
+            .zip(self.notes.iter().zip(self.channels.iter()))
+            .filter_map(move |(voice, (note, ch))| {
+                (note.is_some() && *ch == channel).then_some(voice)
+            })
+    }
+
+    /// bends only the notes played on `channel`, `bend` is between -1.0 and 1.0
+    pub fn set_channel_bend(&mut self, channel: u8, bend: Float) {
+        self.mpe.set_bend(channel, bend);
+        let semitones = self.mpe.bend_semitones(channel);
+
+        self.voices_on(channel)
+            .for_each(|(vco, _env)| vco.osc.set_note_bend(semitones));
+    }
+
+    /// sets the amplitude of only the notes played on `channel`, `pressure` is between 0 and 1.0
+    pub fn set_channel_pressure(&mut self, channel: u8, pressure: Float) {
+        self.mpe.set_pressure(channel, pressure);
+        self.voices_on(channel)
+            .for_each(|(vco, _env)| vco.pressure = pressure);
+    }
+
+    /// moves the filter cutoff of only the notes played on `channel`, `value` is between 0 and 1.0
+    /// (see `EnvelopeFilter::set_timbre`).
+    pub fn set_channel_timbre(&mut self, channel: u8, value: Float) {
+        self.mpe.set_timbre(channel, value);
+        self.voices_on(channel)
+            .for_each(|(_vco, env)| env.set_timbre(value));
+    }
+
+    /// turns MPE on (with every channel in the lower zone) or off.
+    pub fn set_mpe(&mut self, enabled: bool) {
+        self.mpe.set_enabled(enabled);
+    }
+
+    /// re-applies the current tuning to the notes that are being played.
+    pub fn retune(&mut self) {
+        self.oscs
+            .iter_mut()
+            .zip(self.notes.iter())
+            .for_each(|((vco, _env), note)| {
+                if let Some(note) = note {
+                    vco.set_note(*note);
+                }
+            });
+    }
+
+    /// releases every note that is being played.
+    pub fn stop_all(&mut self) {
+        self.notes.iter_mut().for_each(|note| *note = None);
+        self.oscs
+            .iter_mut()
+            .for_each(|(_vco, env)| env.recv_samples(FILTER_OPEN_IN, &vec![0.0]));
+    }
+
+    /// bends the pitch of every voice, `bend` is between -1.0 and 1.0
+    pub fn set_pitch_bend(&mut self, bend: Float) {
+        self.oscs
+            .iter_mut()
+            .for_each(|(vco, _env)| vco.osc.apply_bend(bend));
     }
 
     pub fn set_wave_form(&mut self, wave_form: OscType) {
@@ -119,12 +242,139 @@ impl MidiOsc {
             .for_each(|(vco, _env)| vco.set_osc_type(wave_form));
     }
 
+    pub fn set_wavetable(&mut self, table: Arc<Wavetable>) {
+        self.oscs
+            .iter_mut()
+            .for_each(|(vco, _env)| vco.osc.set_wavetable(table.clone()));
+    }
+
+    /// sets the wavetable position of every voice, 0.0 is the first frame and 1.0 the last.
+    pub fn set_wavetable_position(&mut self, position: Float) {
+        self.oscs
+            .iter_mut()
+            .for_each(|(vco, _env)| vco.set_position(position));
+    }
+
+    /// sets the pulse width of every voice, 0.5 is a square wave.
+    pub fn set_pulse_width(&mut self, width: Float) {
+        self.oscs
+            .iter_mut()
+            .for_each(|(vco, _env)| vco.set_pulse_width(width));
+    }
+
+    /// sets the FM index of every voice, how strongly the FM and PM inputs modulate.
+    pub fn set_fm_index(&mut self, index: Float) {
+        self.oscs
+            .iter_mut()
+            .for_each(|(vco, _env)| vco.set_fm_index(index));
+    }
+
+    /// lets FM push the frequency of every voice below zero.
+    pub fn set_through_zero(&mut self, on: bool) {
+        self.oscs
+            .iter_mut()
+            .for_each(|(vco, _env)| vco.set_through_zero(on));
+    }
+
+    /// sets the number of unison voices each note plays (1 to `osc::MAX_UNISON`).
+    pub fn set_unison(&mut self, n: usize) {
+        self.oscs
+            .iter_mut()
+            .for_each(|(vco, _env)| vco.set_unison(n));
+    }
+
+    /// sets how far apart the unison voices are tuned, 0.0 to 1.0.
+    pub fn set_detune(&mut self, detune: Float) {
+        self.oscs
+            .iter_mut()
+            .for_each(|(vco, _env)| vco.set_detune(detune));
+    }
+
+    /// sets how far apart the unison voices are panned, 0.0 (mono) to 1.0.
+    pub fn set_spread(&mut self, spread: Float) {
+        self.oscs
+            .iter_mut()
+            .for_each(|(vco, _env)| vco.set_spread(spread));
+    }
+
+    /// sets the amplitude and phase of every harmonic played in overtone mode, for every voice.
+    pub fn set_harmonics(&mut self, spectrum: Spectrum) {
+        let harmonics = Arc::new(spectrum);
+
+        self.oscs
+            .iter_mut()
+            .for_each(|(vco, _env)| vco.set_harmonics(harmonics.clone()));
+    }
+
+    /// returns the harmonics played in overtone mode.
+    pub fn harmonics(&self) -> Spectrum {
+        self.oscs
+            .first()
+            .map(|(vco, _env)| vco.osc.harmonics.as_ref().clone())
+            .unwrap_or_default()
+    }
+
+    /// sets the level of every voice's sub oscillator, 0.0 turns them off.
+    pub fn set_sub_level(&mut self, level: Float) {
+        self.oscs
+            .iter_mut()
+            .for_each(|(vco, _env)| vco.set_sub_level(level));
+    }
+
+    /// sets how many octaves (1 or 2) below the note the sub oscillators play.
+    pub fn set_sub_octaves(&mut self, octaves: u8) {
+        self.oscs
+            .iter_mut()
+            .for_each(|(vco, _env)| vco.set_sub_octaves(octaves));
+    }
+
+    pub fn set_sub_waveform(&mut self, waveform: SubWaveform) {
+        self.oscs
+            .iter_mut()
+            .for_each(|(vco, _env)| vco.set_sub_waveform(waveform));
+    }
+
+    /// sets what every voice does when the sync input crosses zero upward.
+    pub fn set_sync_mode(&mut self, mode: SyncMode) {
+        self.oscs
+            .iter_mut()
+            .for_each(|(vco, _env)| vco.set_sync_mode(mode));
+    }
+
     pub fn set_env(&mut self, filter_type: FilterType) {
         self.oscs
             .iter_mut()
             .for_each(|(_vco, env)| env.set_filter_type(filter_type));
     }
 
+    /// sets what the envelopes do when a voice is played again before its release has finished.
+    pub fn set_retrigger_mode(&mut self, mode: RetriggerMode) {
+        self.oscs
+            .iter_mut()
+            .for_each(|(_vco, env)| env.set_retrigger_mode(mode));
+    }
+
+    /// sets the filter response of every voice.
+    pub fn set_filter_mode(&mut self, mode: FilterMode, slope: FilterSlope) {
+        self.oscs
+            .iter_mut()
+            .for_each(|(_vco, env)| env.set_filter_mode(mode, slope));
+    }
+
+    /// sets the breakpoints used by the MSEG envelope of every voice.
+    pub fn set_mseg(&mut self, mseg: &Mseg) {
+        self.oscs
+            .iter_mut()
+            .for_each(|(_vco, env)| env.set_mseg(mseg.clone()));
+    }
+
+    /// sets the tempo in BPM that the MSEG envelope of every voice follows.
+    pub fn set_tempo(&mut self, tempo: Float) {
+        self.oscs
+            .iter_mut()
+            .for_each(|(_vco, env)| env.set_tempo(tempo));
+    }
+
     /// sets volume; inpute is assumed to be between 0 and 1.0
     pub fn set_volume(&mut self, volume: Float) {
         self.oscs
@@ -152,34 +402,95 @@ impl MidiOsc {
         }
     }
 
+    /// sets the release time, input is 0..1 (see `adsr::input_to_seconds`).
+    pub fn set_release(&mut self, release: Float) {
+        if self.oscs[0].1.filter_type == FilterType::ADSR {
+            self.oscs
+                .iter_mut()
+                .for_each(|(_vco, env)| env.recv_samples(adsr::RELEASE_IN, &vec![release]));
+        }
+    }
+
+    /// sets the curve of one envelope segment for every voice.
+    pub fn set_curve(&mut self, segment: EnvSegment, curve: EnvCurve) {
+        self.oscs
+            .iter_mut()
+            .for_each(|(_vco, env)| env.set_curve(segment, curve));
+    }
+
     pub fn set_break(&mut self, threshold: Float) {
-        // if self.oscs[0].1.filter_type == FilterType::ADBDR {
-        //     self.oscs
-        //         .iter_mut()
-        //         .for_each(|(_vco, env)| env.recv_samples(adbdr::DECAY_THRESHOLD, &vec![threshold]));
-        // }
+        if self.oscs[0].1.filter_type == FilterType::ADBDR {
+            self.oscs
+                .iter_mut()
+                .for_each(|(_vco, env)| env.recv_samples(adbdr::DECAY_THRESHOLD, &vec![threshold]));
+        }
     }
 
     pub fn set_decay_2(&mut self, threshold: Float) {
-        // if self.oscs[0].1.filter_type == FilterType::ADBDR {
-        //     self.oscs
-        //         .iter_mut()
-        //         .for_each(|(_vco, env)| env.recv_samples(adbdr::DECAY_2_IN, &vec![threshold]));
-        // }
+        if self.oscs[0].1.filter_type == FilterType::ADBDR {
+            self.oscs
+                .iter_mut()
+                .for_each(|(_vco, env)| env.recv_samples(adbdr::DECAY_2_IN, &vec![threshold]));
+        }
+    }
+
+    /// sets how far the filter envelope moves the cutoff, between -1.0 and 1.0.
+    pub fn set_filter_env_amount(&mut self, amount: Float) {
+        self.oscs
+            .iter_mut()
+            .for_each(|(_vco, env)| env.filter_env_amount = amount.clamp(-1.0, 1.0));
+    }
+
+    /// sets how much the cutoff follows the note, 1.0 is an octave per octave.
+    pub fn set_key_tracking(&mut self, amount: Float) {
+        self.oscs
+            .iter_mut()
+            .for_each(|(_vco, env)| env.key_tracking = amount);
+    }
+
+    /// sets how much softer notes close the filter, between 0 and 1.0.
+    pub fn set_velocity_amount(&mut self, amount: Float) {
+        self.oscs
+            .iter_mut()
+            .for_each(|(_vco, env)| env.velocity_amount = amount.clamp(0.0, 1.0));
+    }
+
+    pub fn set_filter_attack(&mut self, atk: Float) {
+        self.oscs
+            .iter_mut()
+            .for_each(|(_vco, env)| env.set_filter_env(0, atk));
+    }
+
+    pub fn set_filter_decay(&mut self, decay: Float) {
+        self.oscs
+            .iter_mut()
+            .for_each(|(_vco, env)| env.set_filter_env(1, decay));
+    }
+
+    pub fn set_filter_sustain(&mut self, sustain: Float) {
+        self.oscs
+            .iter_mut()
+            .for_each(|(_vco, env)| env.set_filter_env(2, sustain));
+    }
+
+    pub fn set_filter_release(&mut self, release: Float) {
+        self.oscs
+            .iter_mut()
+            .for_each(|(_vco, env)| env.set_filter_env(4, release));
     }
 
     pub fn set_cutoff(&mut self, value: Float) {
         self.oscs
             .iter_mut()
             // .for_each(|(_vco, env)| env.allpass.set_cutoff(value));
-            .for_each(|(_vco, env)| env.filter.set_cutoff(value));
+            .for_each(|(_vco, env)| env.set_cutoff(value));
     }
 
     pub fn set_resonance(&mut self, value: Float) {
         self.oscs
             .iter_mut()
             // .for_each(|(_vco, env)| env.allpass.set_resonance(value));
-            .for_each(|(_vco, env)| env.filter.set_resonance(value));
+            .for_each(|(_vco, env)| env.set_resonance(value));
     }
 }
 
@@ -204,39 +515,40 @@ impl Module for MidiOsc {
     }
 
     fn get_samples(&mut self) -> Vec<(u8, lib::Float)> {
-        let raw_sample: Float = self
+        // the mid (mono) and side signals of every voice each go through the voice's filter.
+        let (raw_sample, raw_side): (Float, Float) = self
             .oscs
             .iter_mut()
             .filter_map(|(vco, env)| {
                 if env.is_pressed() {
-                    let sample: Float = vco
-                        .get_samples()
+                    let (mut sample, mut left, mut right) = (0.0, 0.0, 0.0);
+
+                    vco.get_samples()
                         .into_iter()
-                        .map(|(output, sample)| sample)
-                        .sum();
+                        .for_each(|(output, s)| match output {
+                            vco::AUDIO_OUT => sample += s,
+                            vco::LEFT_OUT => left += s,
+                            vco::RIGHT_OUT => right += s,
+                            _ => {}
+                        });
 
                     env.recv_samples(envelope::AUDIO_IN, &vec![sample]);
 
                     // debug!("{} => {}", env.envelope.get_env(), sample);
 
-                    Some(
-                        env.get_samples()
-                            .into_iter()
-                            .filter_map(|(output, sample)| {
-                                if output == envelope::AUDIO_OUT {
-                                    Some(sample)
-                                } else {
-                                    None
-                                }
-                            })
-                            .sum::<Float>(),
-                    )
+                    let mid: Float = env
+                        .get_samples()
+                        .into_iter()
+                        .filter_map(|(output, s)| (output == envelope::AUDIO_OUT).then_some(s))
+                        .sum();
+
+                    Some((mid, env.filter_side((left - right) * FRAC_1_SQRT_2)))
                 } else {
                     // vco.osc.set_frequency(0.0);
                     None
                 }
             })
-            .sum();
+            .fold((0.0, 0.0), |(mid, side), (m, s)| (mid + m, side + s));
         // .sum();
         let n_notes = self
             .notes
@@ -255,40 +567,69 @@ impl Module for MidiOsc {
         // info!("n_notes {}", n_notes * 2.0 / n_notes.exp());
         // info!("n_notes {}", 0.75 / n_notes.ln_1p());
 
-        let sample = if n_notes > 1.0 {
-            raw_sample / n_notes.sqrt()
+        let (sample, side) = if n_notes > 1.0 {
+            (raw_sample / n_notes.sqrt(), raw_side / n_notes.sqrt())
         } else {
-            raw_sample
+            (raw_sample, raw_side)
         };
 
         // info!("sample {raw_sample} : {sample}");
 
-        vec![(0, sample)]
+        vec![
+            (AUDIO_OUT, sample),
+            (LEFT_OUT, (sample + side) * FRAC_1_SQRT_2),
+            (RIGHT_OUT, (sample - side) * FRAC_1_SQRT_2),
+        ]
     }
 
     fn get_input_names() -> impl Iterator<Item = impl std::fmt::Display> {
-        let mut vco: Vec<String> = Vco::get_input_names()
+        // the envelope inputs come first, see `recv_samples`.
+        let mut env: Vec<String> = EnvelopeFilter::get_input_names()
             .map(|name| format!("{name}"))
             .collect();
-        let mut env: Vec<String> = EnvelopeFilter::get_input_names()
+        let mut vco: Vec<String> = Vco::get_input_names()
             .map(|name| format!("{name}"))
             .collect();
 
-        vco.append(&mut env);
+        env.append(&mut vco);
 
-        vco.into_iter()
+        env.into_iter()
     }
 
     fn get_output_names() -> impl Iterator<Item = impl std::fmt::Display> {
-        let mut vco: Vec<String> = Vco::get_output_names()
-            .map(|name| format!("{name}"))
-            .collect();
-        let mut env: Vec<String> = EnvelopeFilter::get_output_names()
-            .map(|name| format!("{name}"))
-            .collect();
+        ["Audio Out", "Left Out", "Right Out"].iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::envelope::TIMBRE_OCTAVES;
+
+    #[test]
+    fn mpe_expression_sent_before_note_on() {
+        let mut mco = MidiOsc::new(2);
+        mco.set_mpe(true);
+        mco.set_channel_pressure(1, 0.25);
+        mco.set_channel_timbre(1, 1.0);
+        mco.play_note_with_velocity(1, 60, 1.0).unwrap();
+
+        let (vco, env) = &mco.oscs[0];
+        assert_eq!(vco.pressure, 0.25);
+        assert_eq!(env.timbre, 0.5 * TIMBRE_OCTAVES);
+
+        // nothing sent on this channel yet
+        mco.play_note_with_velocity(2, 62, 1.0).unwrap();
+        assert_eq!(mco.oscs[1].0.pressure, 0.0);
+        assert_eq!(mco.oscs[1].1.timbre, 0.0);
+    }
 
-        vco.append(&mut env);
+    #[test]
+    fn full_pressure_without_mpe() {
+        let mut mco = MidiOsc::new(1);
+        mco.set_channel_pressure(1, 0.25);
+        mco.play_note_with_velocity(1, 60, 1.0).unwrap();
 
-        vco.into_iter()
+        assert_eq!(mco.oscs[0].0.pressure, 1.0);
     }
 }
@@ -0,0 +1,135 @@
+use lib::Float;
+use tracing::*;
+
+/// master channel of the lower MPE zone.
+pub const LOWER_MASTER: u8 = 0;
+/// master channel of the upper MPE zone.
+pub const UPPER_MASTER: u8 = 15;
+/// the RPN that holds pitch bend sensitivity.
+pub const RPN_BEND_RANGE: (u8, u8) = (0, 0);
+/// the RPN used by the MPE configuration message.
+pub const RPN_MPE_CONFIG: (u8, u8) = (0, 6);
+/// the "null" RPN, data entry is ignored when it is selected.
+pub const RPN_NULL: (u8, u8) = (127, 127);
+/// default pitch bend range of member channels in semitones.
+pub const DEFAULT_MEMBER_BEND: Float = 48.0;
+
+/// MPE zone configuration and the per channel state needed to take it over MIDI.
+#[derive(Debug, Clone)]
+pub struct Mpe {
+    /// number of member channels of the lower zone (channels 1 and up), 0 when the zone is off.
+    pub lower: u8,
+    /// number of member channels of the upper zone (channels 14 and down), 0 when the zone is off.
+    pub upper: u8,
+    /// pitch bend range of member channels in semitones.
+    pub member_bend_range: Float,
+    /// last pitch bend (-1.0 to 1.0) received on each channel.
+    bends: [Float; 16],
+    /// last channel pressure (0 to 1.0) received on each channel.
+    pressures: [Float; 16],
+    /// last timbre (CC 74, 0 to 1.0) received on each channel.
+    timbres: [Float; 16],
+    /// the RPN selected on each channel (msb, lsb).
+    rpns: [(u8, u8); 16],
+}
+
+impl Default for Mpe {
+    fn default() -> Self {
+        Self {
+            lower: 0,
+            upper: 0,
+            member_bend_range: DEFAULT_MEMBER_BEND,
+            bends: [0.0; 16],
+            pressures: [0.0; 16],
+            timbres: [0.5; 16],
+            rpns: [RPN_NULL; 16],
+        }
+    }
+}
+
+impl Mpe {
+    /// returns true if either zone is on.
+    pub fn enabled(&self) -> bool {
+        self.lower > 0 || self.upper > 0
+    }
+
+    /// returns true if `channel` is a member channel of a zone, ie. its messages only affect the
+    /// notes played on it.
+    pub fn is_member(&self, channel: u8) -> bool {
+        (self.lower > 0 && channel > LOWER_MASTER && channel <= self.lower)
+            || (self.upper > 0 && channel < UPPER_MASTER && channel >= UPPER_MASTER - self.upper)
+    }
+
+    /// sets the number of member channels of the zone whose master is `master`. the other zone
+    /// shrinks if they would overlap.
+    pub fn set_zone(&mut self, master: u8, members: u8) {
+        let members = members.min(15);
+
+        if master == LOWER_MASTER {
+            self.lower = members;
+            self.upper = self.upper.min(14_u8.saturating_sub(members));
+        } else if master == UPPER_MASTER {
+            self.upper = members;
+            self.lower = self.lower.min(14_u8.saturating_sub(members));
+        } else {
+            warn!("channel {master} can't be the master channel of an MPE zone");
+            return;
+        }
+
+        self.member_bend_range = DEFAULT_MEMBER_BEND;
+        info!("MPE zones, lower: {}, upper: {}", self.lower, self.upper);
+    }
+
+    /// turns MPE on with every channel in the lower zone, or turns it off.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.set_zone(UPPER_MASTER, 0);
+        self.set_zone(LOWER_MASTER, if enabled { 15 } else { 0 });
+    }
+
+    pub fn select_rpn_msb(&mut self, channel: u8, value: u8) {
+        self.rpns[channel as usize & 15].0 = value;
+    }
+
+    pub fn select_rpn_lsb(&mut self, channel: u8, value: u8) {
+        self.rpns[channel as usize & 15].1 = value;
+    }
+
+    /// handles a data entry (CC 6) for the selected RPN.
+    pub fn data_entry(&mut self, channel: u8, value: u8) {
+        let rpn = self.rpns[channel as usize & 15];
+
+        if rpn == RPN_MPE_CONFIG {
+            self.set_zone(channel, value);
+        } else if rpn == RPN_BEND_RANGE && self.is_member(channel) {
+            self.member_bend_range = value as Float;
+        }
+    }
+
+    /// stores the pitch bend of `channel` so notes started on it later begin with it.
+    pub fn set_bend(&mut self, channel: u8, bend: Float) {
+        self.bends[channel as usize & 15] = bend;
+    }
+
+    /// the current pitch bend of `channel` in semitones.
+    pub fn bend_semitones(&self, channel: u8) -> Float {
+        self.bends[channel as usize & 15] * self.member_bend_range
+    }
+
+    /// stores the pressure of `channel`, controllers send it just before the note on.
+    pub fn set_pressure(&mut self, channel: u8, pressure: Float) {
+        self.pressures[channel as usize & 15] = pressure;
+    }
+
+    pub fn pressure(&self, channel: u8) -> Float {
+        self.pressures[channel as usize & 15]
+    }
+
+    /// stores the timbre of `channel`, controllers send it just before the note on.
+    pub fn set_timbre(&mut self, channel: u8, timbre: Float) {
+        self.timbres[channel as usize & 15] = timbre;
+    }
+
+    pub fn timbre(&self, channel: u8) -> Float {
+        self.timbres[channel as usize & 15]
+    }
+}
@@ -0,0 +1,61 @@
+use crate::{
+    Float,
+    common::Module,
+    osc::noise::{Color, Noise},
+};
+use tracing::*;
+
+pub const N_INPUTS: u8 = 1;
+pub const N_OUTPUTS: u8 = 3;
+
+pub const VOLUME_INPUT: u8 = 0;
+
+pub const WHITE_OUT: u8 = 0;
+pub const PINK_OUT: u8 = 1;
+pub const BROWN_OUT: u8 = 2;
+
+/// a noise source with an output for each color of noise.
+pub struct NoiseModule {
+    /// one generator per output so the outputs aren't correlated.
+    white: Noise,
+    pink: Noise,
+    brown: Noise,
+    volume_in: Float,
+}
+
+impl NoiseModule {
+    pub fn new() -> Self {
+        Self {
+            white: Noise::new(),
+            pink: Noise::new(),
+            brown: Noise::new(),
+            volume_in: 1.0,
+        }
+    }
+}
+
+impl Module for NoiseModule {
+    fn get_samples(&mut self) -> Vec<(u8, Float)> {
+        vec![
+            (WHITE_OUT, self.white.next(Color::White) * self.volume_in),
+            (PINK_OUT, self.pink.next(Color::Pink) * self.volume_in),
+            (BROWN_OUT, self.brown.next(Color::Brown) * self.volume_in),
+        ]
+    }
+
+    fn recv_samples(&mut self, input_n: u8, samples: &[Float]) {
+        if input_n == VOLUME_INPUT {
+            self.volume_in = (samples.iter().sum::<Float>().tanh() + 1.0) * 0.5;
+        } else {
+            error!("invalid input: {input_n} for the Noise module");
+        }
+    }
+
+    fn get_input_names() -> impl Iterator<Item = impl std::fmt::Display> {
+        ["Vol."].iter()
+    }
+
+    fn get_output_names() -> impl Iterator<Item = impl std::fmt::Display> {
+        ["White", "Pink", "Brown"].iter()
+    }
+}
@@ -0,0 +1,209 @@
+use crate::{Float, SAMPLE_RATE};
+
+/// a phase accumulator that runs from 0.0 to 1.0 once per cycle.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Phase {
+    /// where in the cycle the oscillator is.
+    pub phase: Float,
+    /// how far the phase moves per sample, ie. frequency / sample rate. negative when the phase
+    /// runs backwards (through zero FM).
+    pub inc: Float,
+    /// added to the phase when it is read, used for phase modulation.
+    pub offset: Float,
+    /// the phase runs backwards, toggled by soft sync.
+    pub reversed: bool,
+    /// the last step started a new cycle.
+    pub wrapped: bool,
+}
+
+impl Phase {
+    pub fn set_frequency(&mut self, frequency: Float) {
+        let inc = frequency / SAMPLE_RATE as Float;
+        self.inc = if self.reversed { -inc } else { inc };
+    }
+
+    /// jumps to `phase` (in cycles), 0.0 restarts the cycle (hard sync).
+    pub fn set(&mut self, phase: Float) {
+        self.phase = phase.rem_euclid(1.0);
+    }
+
+    /// makes the phase run the other way (soft sync).
+    pub fn reverse(&mut self) {
+        self.reversed = !self.reversed;
+        self.inc = -self.inc;
+    }
+
+    /// returns the current phase (with the offset applied) then advances it one sample.
+    pub fn step(&mut self) -> Float {
+        self.step_by(1.0)
+    }
+
+    /// like `step` but the increment is scaled by `ratio`, used for frequency modulation so the
+    /// increment doesn't have to be worked out again every sample.
+    pub fn step_by(&mut self, ratio: Float) -> Float {
+        let phase = (self.phase + self.offset).rem_euclid(1.0);
+        self.phase += self.inc * ratio;
+        self.wrapped = !(0.0..1.0).contains(&self.phase);
+        self.phase -= self.phase.floor();
+
+        phase
+    }
+}
+
+/// polynomial band-limited step (PolyBLEP). the correction for a step of height 2 at phase 0, added
+/// around each discontinuity of a naive waveform so it doesn't alias. `t` is the phase and `dt` the
+/// phase increment.
+pub fn poly_blep(t: Float, dt: Float) -> Float {
+    // the correction is the same whichever way the phase runs.
+    let dt = dt.abs();
+
+    if dt <= 0.0 {
+        0.0
+    } else if t < dt {
+        let t = t / dt;
+        t + t - t * t - 1.0
+    } else if t > 1.0 - dt {
+        let t = (t - 1.0) / dt;
+        t * t + t + t + 1.0
+    } else {
+        0.0
+    }
+}
+
+/// polynomial band-limited ramp (PolyBLAMP). the correction for a change in slope of 2 per sample at
+/// phase 0 (the integral of `poly_blep`).
+pub fn poly_blamp(t: Float, dt: Float) -> Float {
+    let dt = dt.abs();
+
+    if dt <= 0.0 {
+        0.0
+    } else if t < dt {
+        let t = 1.0 - t / dt;
+        t * t * t / 3.0
+    } else if t > 1.0 - dt {
+        let t = (t - 1.0) / dt + 1.0;
+        t * t * t / 3.0
+    } else {
+        0.0
+    }
+}
+
+/// a band-limited saw tooth, ramps from -1.0 to 1.0.
+pub fn saw(t: Float, dt: Float) -> Float {
+    2.0 * t - 1.0 - poly_blep(t, dt)
+}
+
+/// a band-limited pulse that is high for `width` (0.0 to 1.0) of the cycle.
+pub fn pulse(t: Float, dt: Float, width: Float) -> Float {
+    let naive = if t < width { 1.0 } else { -1.0 };
+
+    naive + poly_blep(t, dt) - poly_blep((t + 1.0 - width) % 1.0, dt)
+}
+
+/// a band-limited triangle, -1.0 at phase 0 and 1.0 at phase 0.5.
+pub fn triangle(t: Float, dt: Float) -> Float {
+    let naive = 1.0 - 4.0 * (t - 0.5).abs();
+
+    naive + 4.0 * dt.abs() * (poly_blamp(t, dt) - poly_blamp((t + 0.5) % 1.0, dt))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const DT: Float = 0.01;
+    const EPS: Float = 1e-6;
+
+    fn close(a: Float, b: Float) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    /// the average of `wave` over one cycle.
+    fn mean(wave: impl Fn(Float) -> Float) -> Float {
+        (0..1000).map(|i| wave(i as Float / 1000.0)).sum::<Float>() / 1000.0
+    }
+
+    #[test]
+    fn corrections_only_touch_the_discontinuity() {
+        for t in [2.0 * DT, 0.5, 1.0 - 2.0 * DT] {
+            assert_eq!(poly_blep(t, DT), 0.0);
+            assert_eq!(poly_blamp(t, DT), 0.0);
+        }
+
+        assert_eq!(poly_blep(0.0, 0.0), 0.0);
+        assert_eq!(poly_blamp(0.0, 0.0), 0.0);
+        // same either way the phase runs
+        assert_eq!(poly_blep(0.5 * DT, -DT), poly_blep(0.5 * DT, DT));
+    }
+
+    #[test]
+    fn corrections_are_continuous() {
+        assert!(close(poly_blep(DT - EPS, DT), 0.0));
+        assert!(close(poly_blep(1.0 - DT + EPS, DT), 0.0));
+        assert!(close(poly_blep(EPS, DT), -1.0));
+        assert!(close(poly_blep(1.0 - EPS, DT), 1.0));
+        assert!(close(poly_blamp(EPS, DT), poly_blamp(1.0 - EPS, DT)));
+        assert!(close(poly_blamp(DT - EPS, DT), 0.0));
+    }
+
+    #[test]
+    fn saw_has_no_jump_at_the_wrap() {
+        assert!(close(saw(1.0 - EPS, DT), saw(EPS, DT)));
+        assert!(close(saw(0.5, DT), 0.0));
+        assert!(mean(|t| saw(t, DT)).abs() < 0.01);
+    }
+
+    #[test]
+    fn pulse_wave() {
+        assert!(close(pulse(0.25, DT, 0.5), 1.0));
+        assert!(close(pulse(0.75, DT, 0.5), -1.0));
+        assert!(close(pulse(1.0 - EPS, DT, 0.5), pulse(EPS, DT, 0.5)));
+        assert!(close(mean(|t| pulse(t, DT, 0.25)), -0.5));
+    }
+
+    #[test]
+    fn triangle_wave() {
+        assert!(close(triangle(0.25, DT), 0.0));
+        assert!(close(triangle(1.0 - EPS, DT), triangle(EPS, DT)));
+        assert!(triangle(0.5, DT) > 0.95);
+        assert!(mean(|t| triangle(t, DT)).abs() < 0.01);
+    }
+
+    #[test]
+    fn phase() {
+        let mut phase = Phase::default();
+        phase.set_frequency(SAMPLE_RATE as Float / 4.0);
+
+        assert_eq!(phase.step(), 0.0);
+        assert!(!phase.wrapped);
+        (0..3).for_each(|_| _ = phase.step());
+        assert!(phase.wrapped);
+        assert!(close(phase.phase, 0.0));
+
+        phase.set(1.25);
+        assert!(close(phase.phase, 0.25));
+
+        phase.reverse();
+        assert!(phase.inc < 0.0);
+        phase.step();
+        phase.step();
+        assert!(phase.wrapped);
+        assert!(close(phase.phase, 0.75));
+
+        phase.offset = 0.5;
+        assert!(close(phase.step(), 0.25));
+    }
+
+    #[test]
+    fn phase_ratio() {
+        let mut phase = Phase::default();
+        phase.set_frequency(SAMPLE_RATE as Float / 8.0);
+
+        phase.step_by(2.0);
+        assert!(close(phase.phase, 0.25));
+        assert!(close(phase.inc, 0.125));
+
+        phase.step_by(-1.0);
+        assert!(close(phase.phase, 0.125));
+    }
+}
@@ -1,16 +1,32 @@
-use crate::{common::bend_range, Float};
-pub use lib::OscType;
-use std::sync::Arc;
+use crate::{Float, SAMPLE_RATE, common::bend_range};
+pub use lib::{OscType, SyncMode};
+pub use spectrum::Spectrum;
+use std::{
+    f32::consts::{FRAC_1_SQRT_2, FRAC_PI_4},
+    sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    },
+};
+use sub::{SubOscillator, SubWaveform};
 use tracing::trace;
+use wavetable::Wavetable;
 
+pub mod blep;
+pub mod noise;
 pub mod saw;
 pub mod sine;
+pub mod spectrum;
 pub mod square;
+pub mod sub;
 pub mod triangle;
+pub mod wavetable;
 
-pub type Harmonics = Arc<[f32; HARMONICS_SIZE]>;
+pub type Harmonics = Arc<Spectrum>;
 
 pub const HARMONICS_SIZE: usize = 20;
+/// the highest frequency that can be produced at the sample rate.
+pub const NYQUIST: Float = SAMPLE_RATE as Float / 2.0;
 // pub const DEFAULT_HARMONICS: [f32; HARMONICS_SIZE] = [
 //     0.700, 0.243, 0.229, 0.095, 0.139, 0.087, 0.288, 0.199, 0.124, 0.090,
 // ];
@@ -76,51 +92,194 @@ pub trait Osc: Send {
 
     /// generate a sample when not in overtone mode
     fn puretone_sample(&mut self) -> Float;
+
+    /// jumps to `phase` (in cycles) of the fundamental.
+    fn set_phase(&mut self, _phase: Float) {}
+
+    /// restarts the cycle, used for hard sync.
+    fn reset_phase(&mut self) {
+        self.set_phase(0.0);
+    }
+
+    /// makes the cycle run the other way, used for soft sync.
+    fn reverse_phase(&mut self) {}
+
+    /// returns true if the last sample started a new cycle.
+    fn cycle_started(&self) -> bool {
+        false
+    }
+
+    /// sets the harmonics played in overtone mode.
+    fn set_harmonics(&mut self, _harmonics: Harmonics) {}
+
+    /// scales the frequency by `ratio` without working out the phase increments again, used for
+    /// frequency modulation.
+    fn set_fm_ratio(&mut self, _ratio: Float) {}
+
+    /// offsets the phase of the fundamental by `offset` cycles, used for phase modulation.
+    fn set_phase_offset(&mut self, _offset: Float) {}
+
+    /// sets where in the wavetable to play from (0.0 to 1.0), only used by wavetable oscillators.
+    fn set_position(&mut self, _position: Float) {}
+
+    /// sets the wavetable to play, only used by wavetable oscillators.
+    fn set_wavetable(&mut self, _table: Arc<Wavetable>) {}
+
+    /// sets how much of the cycle the wave is high for (0.0 to 1.0), only used by pulse
+    /// oscillators.
+    fn set_pulse_width(&mut self, _width: Float) {}
 }
 
+/// the most unison voices an oscillator can have.
+pub const MAX_UNISON: usize = 8;
+/// how far (in semitones) the outermost unison voices are detuned when detune is at 1.0.
+pub const MAX_DETUNE: Float = 1.0;
+
 pub struct Oscillator {
-    pub osc: Box<dyn Osc>,
+    /// one oscillator per unison voice.
+    pub oscs: Vec<Box<dyn Osc>>,
     pub waveform: OscType,
     pub overtones: bool,
     pub frequency: Float,
     pub bend: Float,
+    /// frequency multiplier from the pitch wheel.
+    pub bend_ratio: Float,
+    /// frequency multiplier from a per note pitch bend (MPE).
+    pub note_bend: Float,
+    /// frequency multiplier from frequency modulation, negative when the oscillator runs backwards
+    /// (through zero FM).
+    pub fm_ratio: Float,
+    /// phase offset (in cycles) from phase modulation.
+    pub pm_offset: Float,
     pub volume: Float,
+    /// the table used when the waveform is `OscType::Wavetable`.
+    pub wavetable: Arc<Wavetable>,
+    /// where in the wavetable to play from.
+    pub position: Float,
+    /// the pulse width used when the waveform is `OscType::Square`.
+    pub pulse_width: Float,
+    /// how far apart the unison voices are tuned, 0.0 to 1.0.
+    pub detune: Float,
+    /// frequency multiplier of each unison voice from the detune.
+    detunes: Vec<Float>,
+    /// how far apart the unison voices are panned, 0.0 (mono) to 1.0.
+    pub spread: Float,
+    /// (left, right) gain of each unison voice.
+    pans: Vec<(Float, Float)>,
+    /// plays below the unison voices, in the center.
+    pub sub: SubOscillator,
+    /// the harmonics played in overtone mode.
+    pub harmonics: Harmonics,
 }
 
 impl Oscillator {
     pub fn new() -> Self {
+        let mut osc = Self {
+            oscs: Vec::new(),
+            waveform: OscType::Sine,
+            overtones: true,
+            frequency: 0.0,
+            bend: bend_range(),
+            bend_ratio: 1.0,
+            note_bend: 1.0,
+            fm_ratio: 1.0,
+            pm_offset: 0.0,
+            volume: 1.0,
+            wavetable: wavetable::default_table(),
+            position: 0.0,
+            pulse_width: 0.5,
+            detune: 0.0,
+            detunes: Vec::new(),
+            spread: 0.0,
+            pans: Vec::new(),
+            sub: SubOscillator::default(),
+            harmonics: Harmonics::default(),
+        };
         // trace!("making a sine wave oscillator");
-        let mut osc = Box::new(sine::Oscillator::default());
-        // trace!("made a sine wave oscillator");
-        osc.init();
-        osc.enable_overtones(true);
-        let waveform = OscType::Sine;
-        let overtones = true;
-        let frequency = 0.0;
-        let bend = bend_range();
-        let volume = 1.0;
-
-        Self {
-            osc,
-            waveform,
-            overtones,
-            frequency,
-            bend,
-            volume,
-        }
+        osc.set_unison(1);
+
+        osc
     }
 
     pub fn set_frequency(&mut self, frequency: Float) {
         self.frequency = frequency;
-        self.osc.set_frequency(frequency);
+        self.update_frequency();
+    }
+
+    /// where unison voice `i` sits between -1.0 and 1.0, used for its detune and pan.
+    fn voice_offset(&self, i: usize) -> Float {
+        let n = self.oscs.len();
+
+        if n > 1 {
+            i as Float * 2.0 / (n - 1) as Float - 1.0
+        } else {
+            0.0
+        }
+    }
+
+    /// sets the frequency of the underlying oscillators to the note frequency with bends and
+    /// detune applied.
+    fn update_frequency(&mut self) {
+        let frequency = self.frequency * self.bend_ratio * self.note_bend;
+
+        self.oscs
+            .iter_mut()
+            .zip(self.detunes.iter())
+            .for_each(|(osc, detune)| osc.set_frequency(frequency * detune));
+        self.sub.set_frequency(frequency);
+    }
+
+    /// works out the frequency multiplier of every unison voice.
+    fn update_detunes(&mut self) {
+        self.detunes = (0..self.oscs.len())
+            .map(|i| (2.0 as Float).powf(self.voice_offset(i) * self.detune * MAX_DETUNE / 12.0))
+            .collect();
+    }
+
+    /// works out the pan of every unison voice (equal power).
+    fn update_pans(&mut self) {
+        self.pans = (0..self.oscs.len())
+            .map(|i| {
+                let angle = (self.voice_offset(i) * self.spread + 1.0) * FRAC_PI_4;
+
+                (angle.cos(), angle.sin())
+            })
+            .collect();
+    }
+
+    /// returns the next (left, right) sample.
+    pub fn get_stereo_sample(&mut self) -> (Float, Float) {
+        let gain = self.volume
+            * (1.0 / (HARMONICS_SIZE as Float).sqrt())
+            * (1.0 / (self.oscs.len() as Float).sqrt());
+
+        let (left, right) = self.oscs.iter_mut().zip(self.pans.iter()).fold(
+            (0.0, 0.0),
+            |(left, right), (osc, (l, r))| {
+                let sample = osc.get_sample();
+
+                (left + sample * l, right + sample * r)
+            },
+        );
+
+        // the sub isn't a unison voice so it isn't scaled down with them.
+        let sub = self.sub.get_sample()
+            * self.volume
+            * (1.0 / (HARMONICS_SIZE as Float).sqrt())
+            * FRAC_1_SQRT_2;
+
+        (left * gain + sub, right * gain + sub)
     }
 
     pub fn get_sample(&mut self) -> Float {
-        self.osc.get_sample() * self.volume * (1.0 / (HARMONICS_SIZE as Float).sqrt())
+        let (left, right) = self.get_stereo_sample();
+
+        (left + right) * FRAC_1_SQRT_2
     }
 
-    pub fn set_waveform(&mut self, waveform: OscType) {
-        self.osc = match waveform {
+    /// makes an oscillator of the current waveform with the current settings.
+    fn make_osc(&self) -> Box<dyn Osc> {
+        let mut osc: Box<dyn Osc> = match self.waveform {
             OscType::Sine => {
                 let mut osc = sine::Oscillator::default();
                 osc.init();
@@ -129,6 +288,7 @@ impl Oscillator {
             OscType::Square => {
                 let mut osc = square::Oscillator::default();
                 osc.init();
+                osc.set_pulse_width(self.pulse_width);
                 Box::new(osc)
             }
             OscType::Triangle => {
@@ -141,34 +301,191 @@ impl Oscillator {
                 osc.init();
                 Box::new(osc)
             }
+            OscType::Wavetable => {
+                let mut osc = wavetable::Oscillator::new(self.wavetable.clone());
+                osc.set_position(self.position);
+                Box::new(osc)
+            }
+            OscType::WhiteNoise => Box::new(noise::Oscillator::new(noise::Color::White)),
+            OscType::PinkNoise => Box::new(noise::Oscillator::new(noise::Color::Pink)),
+            OscType::BrownNoise => Box::new(noise::Oscillator::new(noise::Color::Brown)),
         };
 
-        self.osc.enable_overtones(true);
+        osc.enable_overtones(self.overtones);
+        osc.set_harmonics(self.harmonics.clone());
+        osc.set_phase_offset(self.pm_offset);
+        osc.set_fm_ratio(self.fm_ratio);
+
+        osc
+    }
+
+    /// rebuilds the oscillators, every unison voice after the first starts at a random phase so
+    /// they don't all line up.
+    fn rebuild(&mut self, n: usize) {
+        self.oscs = (0..n)
+            .map(|i| {
+                let mut osc = self.make_osc();
+
+                if i > 0 {
+                    osc.set_phase(random_phase());
+                }
 
-        self.osc.set_frequency(self.frequency);
+                osc
+            })
+            .collect();
+
+        self.update_pans();
+        self.update_detunes();
+        self.update_frequency();
+    }
+
+    pub fn set_waveform(&mut self, waveform: OscType) {
+        self.waveform = waveform;
+        self.rebuild(self.oscs.len());
+    }
+
+    /// sets the number of unison voices (1 to `MAX_UNISON`).
+    pub fn set_unison(&mut self, n: usize) {
+        self.rebuild(n.clamp(1, MAX_UNISON));
+    }
+
+    /// sets how far apart the unison voices are tuned, 0.0 to 1.0.
+    pub fn set_detune(&mut self, detune: Float) {
+        self.detune = detune.clamp(0.0, 1.0);
+        self.update_detunes();
+        self.update_frequency();
+    }
+
+    /// sets how far apart the unison voices are panned, 0.0 (mono) to 1.0.
+    pub fn set_spread(&mut self, spread: Float) {
+        self.spread = spread.clamp(0.0, 1.0);
+        self.update_pans();
+    }
+
+    /// sets the level of the sub oscillator, 0.0 turns it off.
+    pub fn set_sub_level(&mut self, level: Float) {
+        self.sub.level = level.clamp(0.0, 1.0);
+    }
+
+    /// sets how many octaves (1 or 2) below the main note the sub oscillator plays.
+    pub fn set_sub_octaves(&mut self, octaves: u8) {
+        self.sub.set_octaves(octaves);
+    }
+
+    pub fn set_sub_waveform(&mut self, waveform: SubWaveform) {
+        self.sub.waveform = waveform;
     }
 
     /// applies a pitch bend by changing the oscilators frequency
     pub fn apply_bend(&mut self, bend: Float) {
-        let note = self.frequency;
-
-        let new_note = if bend > 0.0 {
-            let shift = note * self.bend;
-            note + bend * (shift - note)
+        self.bend_ratio = if bend > 0.0 {
+            1.0 + bend * (self.bend - 1.0)
         } else if bend < 0.0 {
-            let shift = note / self.bend;
-            note + bend * (note - shift)
+            1.0 + bend * (1.0 - 1.0 / self.bend)
         } else {
-            note
+            1.0
         };
 
         // info!("{new_note}");
 
-        self.osc.set_frequency(new_note);
+        self.update_frequency();
+    }
+
+    /// bends just this note by `semitones`, used for per note pitch bend.
+    pub fn set_note_bend(&mut self, semitones: Float) {
+        self.note_bend = (2.0 as Float).powf(semitones / 12.0);
+        self.update_frequency();
+    }
+
+    /// applies frequency and phase modulation for the next sample. `fm` scales the frequency by
+    /// `1 + fm`, `pm` is a phase offset in cycles. without `through_zero` the frequency stops at 0
+    /// instead of going negative.
+    pub fn modulate(&mut self, fm: Float, pm: Float, through_zero: bool) {
+        let fm_ratio = if through_zero {
+            1.0 + fm
+        } else {
+            (1.0 + fm).max(0.0)
+        };
+
+        if fm_ratio != self.fm_ratio {
+            self.fm_ratio = fm_ratio;
+            self.oscs
+                .iter_mut()
+                .for_each(|osc| osc.set_fm_ratio(fm_ratio));
+            self.sub.fm_ratio = fm_ratio;
+        }
+
+        if pm != self.pm_offset {
+            self.pm_offset = pm;
+            self.oscs
+                .iter_mut()
+                .for_each(|osc| osc.set_phase_offset(pm));
+        }
+    }
+
+    /// restarts the cycle (hard sync) or reverses it (soft sync).
+    pub fn sync(&mut self, mode: SyncMode) {
+        self.oscs.iter_mut().for_each(|osc| match mode {
+            SyncMode::Hard => osc.reset_phase(),
+            SyncMode::Soft => osc.reverse_phase(),
+        });
+    }
+
+    /// returns true if the last sample started a new cycle.
+    pub fn cycle_started(&self) -> bool {
+        self.oscs[0].cycle_started()
+    }
+
+    /// sets the wavetable played when the waveform is `OscType::Wavetable`.
+    pub fn set_wavetable(&mut self, table: Arc<Wavetable>) {
+        self.wavetable = table.clone();
+        self.oscs
+            .iter_mut()
+            .for_each(|osc| osc.set_wavetable(table.clone()));
+    }
+
+    /// sets where in the wavetable to play from, 0.0 is the first frame and 1.0 the last.
+    pub fn set_position(&mut self, position: Float) {
+        self.position = position.clamp(0.0, 1.0);
+        self.oscs
+            .iter_mut()
+            .for_each(|osc| osc.set_position(self.position));
+    }
+
+    /// sets the pulse width, 0.5 is a square wave.
+    pub fn set_pulse_width(&mut self, width: Float) {
+        self.pulse_width = width.clamp(square::MIN_WIDTH, 1.0 - square::MIN_WIDTH);
+        self.oscs
+            .iter_mut()
+            .for_each(|osc| osc.set_pulse_width(self.pulse_width));
+    }
+
+    /// sets the amplitude and phase of every harmonic played in overtone mode.
+    pub fn set_harmonics(&mut self, harmonics: Harmonics) {
+        self.harmonics = harmonics;
+        self.oscs
+            .iter_mut()
+            .for_each(|osc| osc.set_harmonics(self.harmonics.clone()));
     }
 
     pub fn enable_overtones(&mut self, enabled: bool) {
         self.overtones = enabled;
-        self.osc.enable_overtones(enabled);
+        self.oscs
+            .iter_mut()
+            .for_each(|osc| osc.enable_overtones(enabled));
     }
 }
+
+/// a random phase (0.0 to 1.0) from a small xorshift generator, good enough to spread out unison
+/// voices.
+fn random_phase() -> Float {
+    static STATE: AtomicU32 = AtomicU32::new(0x9E37_79B9);
+
+    let mut x = STATE.load(Ordering::Relaxed);
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    STATE.store(x, Ordering::Relaxed);
+
+    (x >> 8) as Float / (1 << 24) as Float
+}
@@ -0,0 +1,178 @@
+use super::{Osc, blep::Phase};
+use crate::Float;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// below this frequency noise is sampled and held, changing once per cycle instead of every sample,
+/// so an LFO can make random steps.
+pub const SAMPLE_AND_HOLD_LIMIT: Float = 20.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    /// equal power at every frequency.
+    White,
+    /// -3 dB per octave.
+    Pink,
+    /// -6 dB per octave (brownian/red noise).
+    Brown,
+}
+
+/// a noise generator that can make white, pink and brown noise.
+#[derive(Debug, Clone)]
+pub struct Noise {
+    /// xorshift state, never 0.
+    state: u32,
+    /// the state of the pink noise filter.
+    pink: [Float; 7],
+    /// the state of the brown noise integrator.
+    brown: Float,
+}
+
+impl Default for Noise {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Noise {
+    /// makes a generator with a different seed from every other generator.
+    pub fn new() -> Self {
+        static SEED: AtomicU32 = AtomicU32::new(0x2545_F491);
+
+        let mut seed = SEED.fetch_add(0x9E37_79B9, Ordering::Relaxed);
+        seed ^= seed >> 16;
+        seed = seed.wrapping_mul(0x7FEB_352D);
+        seed ^= seed >> 15;
+
+        Self {
+            state: seed.max(1),
+            pink: [0.0; 7],
+            brown: 0.0,
+        }
+    }
+
+    /// white noise between -1.0 and 1.0.
+    pub fn white(&mut self) -> Float {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+
+        (x >> 8) as Float / (1 << 23) as Float - 1.0
+    }
+
+    /// pink noise, Paul Kellet's refined filter.
+    pub fn pink(&mut self) -> Float {
+        let white = self.white();
+        let b = &mut self.pink;
+
+        b[0] = 0.99886 * b[0] + white * 0.0555179;
+        b[1] = 0.99332 * b[1] + white * 0.0750759;
+        b[2] = 0.96900 * b[2] + white * 0.1538520;
+        b[3] = 0.86650 * b[3] + white * 0.3104856;
+        b[4] = 0.55000 * b[4] + white * 0.5329522;
+        b[5] = -0.7616 * b[5] - white * 0.0168980;
+        let pink = b[0] + b[1] + b[2] + b[3] + b[4] + b[5] + b[6] + white * 0.5362;
+        b[6] = white * 0.115926;
+
+        pink * 0.11
+    }
+
+    /// brown noise, leaky integrated white noise.
+    pub fn brown(&mut self) -> Float {
+        let white = self.white();
+        self.brown = (self.brown + 0.02 * white) / 1.02;
+
+        self.brown * 3.5
+    }
+
+    pub fn next(&mut self, color: Color) -> Float {
+        match color {
+            Color::White => self.white(),
+            Color::Pink => self.pink(),
+            Color::Brown => self.brown(),
+        }
+    }
+}
+
+/// an oscillator that plays noise. the frequency only matters below `SAMPLE_AND_HOLD_LIMIT`.
+#[derive(Debug, Clone)]
+pub struct Oscillator {
+    color: Color,
+    noise: Noise,
+    phase: Phase,
+    frequency: Float,
+    /// the sampled and held value.
+    held: Float,
+    volume: Float,
+    overtones: bool,
+    /// frequency multiplier from frequency modulation, applied when the phase steps.
+    fm_ratio: Float,
+}
+
+impl Oscillator {
+    pub fn new(color: Color) -> Self {
+        Self {
+            color,
+            noise: Noise::new(),
+            phase: Phase::default(),
+            frequency: 0.0,
+            held: 0.0,
+            volume: 1.0,
+            overtones: false,
+            fm_ratio: 1.0,
+        }
+    }
+}
+
+impl Osc for Oscillator {
+    fn puretone_sample(&mut self) -> Float {
+        let frequency = self.frequency * self.fm_ratio;
+
+        if frequency != 0.0 && frequency.abs() < SAMPLE_AND_HOLD_LIMIT {
+            self.phase.step_by(self.fm_ratio);
+
+            if self.phase.wrapped {
+                self.held = self.noise.next(self.color);
+            }
+
+            self.held * self.volume
+        } else {
+            self.noise.next(self.color) * self.volume
+        }
+    }
+
+    fn overtones_sample(&mut self) -> Float {
+        // noise has no harmonics.
+        self.puretone_sample()
+    }
+
+    fn set_frequency(&mut self, frequency: Float) {
+        self.frequency = frequency;
+        self.phase.set_frequency(frequency);
+    }
+
+    fn set_fm_ratio(&mut self, ratio: Float) {
+        self.fm_ratio = ratio;
+    }
+
+    fn enable_overtones(&mut self, enabled: bool) {
+        self.overtones = enabled;
+    }
+
+    fn overtones(&mut self) -> bool {
+        self.overtones
+    }
+
+    fn set_phase(&mut self, phase: Float) {
+        self.phase.set(phase);
+    }
+
+    fn reverse_phase(&mut self) {
+        self.phase.reverse();
+    }
+
+    fn cycle_started(&self) -> bool {
+        self.phase.wrapped
+    }
+}
@@ -1,103 +1,75 @@
-use super::{Harmonics, Osc, DEFAULT_HARMONICS, HARMONICS_SIZE};
+use super::{
+    HARMONICS_SIZE, Harmonics, Osc,
+    blep::{self, Phase},
+};
 use crate::Float;
-use fon::chan::Channel;
-use twang::noise::White;
-use twang::ops::Gain;
-use twang::osc::Sawtooth;
 
 #[derive(Clone, Debug, Default)]
 pub struct Oscillator {
-    piano: [Sawtooth; HARMONICS_SIZE],
-    frequency: f32,
-    volume: f32,
+    phases: [Phase; HARMONICS_SIZE],
+    frequency: Float,
+    volume: Float,
     overtones: bool,
     harmonics: Harmonics,
+    /// frequency multiplier from frequency modulation, applied when the phases step.
+    fm_ratio: Float,
 }
 
-// impl Default for Oscillator {
-//     fn default() -> Self {
-//         Self {
-//             harmonics: DEFAULT_HARMONICS.into(),
-//             ..Default::default()
-//         }
-//     }
-// }
-
 impl Oscillator {
     pub fn init(&mut self) {
         self.volume = 1.0;
-        self.harmonics = DEFAULT_HARMONICS.into();
-
-        let mut white_noise = White::default();
-
-        // Adjust phases of harmonics.
-        for harmonic in self.piano.iter_mut() {
-            harmonic.shift(white_noise.step());
-        }
+        self.harmonics = Harmonics::default();
+        self.fm_ratio = 1.0;
     }
-
-    // fn puretone_sample(&mut self) -> Float {
-    //     let sample: f32 = Gain
-    //         .step(self.piano[0].step(self.frequency), self.volume.into())
-    //         .to_f32();
-    //
-    //     sample as Float
-    // }
-
-    // fn overtones_sample(&mut self) -> Float {
-    //     // self.puretone_sample()
-    //     let sample: f32 = self
-    //         .piano
-    //         .iter_mut()
-    //         .enumerate()
-    //         .zip(self.harmonics.iter())
-    //         .map(|((i, o), v)| {
-    //             // Get next sample from oscillator.
-    //             let sample = o.step(self.frequency * (i + 1) as f32);
-    //             // Pan the generated harmonic center
-    //             Gain.step(sample, (v * self.volume).into()).to_f32()
-    //         })
-    //         .sum();
-    //     sample as Float
-    // }
 }
 
 impl Osc for Oscillator {
-    // fn get_sample(&mut self) -> Float {
-    //     if self.overtones {
-    //         self.overtones_sample()
-    //     } else {
-    //         self.puretone_sample()
-    //     }
-    // }
+    fn puretone_sample(&mut self) -> Float {
+        let dt = self.phases[0].inc * self.fm_ratio;
+
+        blep::saw(self.phases[0].step_by(self.fm_ratio), dt) * self.volume
+    }
 
     fn overtones_sample(&mut self) -> Float {
-        // self.puretone_sample()
-        let sample: f32 = self
-            .piano
+        self.phases
             .iter_mut()
-            .enumerate()
             .zip(self.harmonics.iter())
-            .map(|((i, o), v)| {
-                // Get next sample from oscillator.
-                let sample = o.step(self.frequency * (i + 1) as f32);
-                // Pan the generated harmonic center
-                Gain.step(sample, (v * self.volume).into()).to_f32()
+            .map(|(phase, (v, p))| {
+                let dt = phase.inc * self.fm_ratio;
+                let t = (phase.step_by(self.fm_ratio) + p).rem_euclid(1.0);
+
+                // harmonics at or above nyquist would alias, they are muted but keep running so
+                // they come back in phase.
+                if dt.abs() < 0.5 {
+                    blep::saw(t, dt) * v * self.volume
+                } else {
+                    0.0
+                }
             })
-            .sum();
-        sample as Float
+            .sum()
     }
 
-    fn puretone_sample(&mut self) -> Float {
-        let sample: f32 = Gain
-            .step(self.piano[0].step(self.frequency), self.volume.into())
-            .to_f32();
+    fn set_frequency(&mut self, frequency: Float) {
+        self.frequency = frequency;
+        self.phases
+            .iter_mut()
+            .enumerate()
+            .for_each(|(i, phase)| phase.set_frequency(frequency * (i + 1) as Float));
+    }
 
-        sample as Float
+    fn set_fm_ratio(&mut self, ratio: Float) {
+        self.fm_ratio = ratio;
     }
 
-    fn set_frequency(&mut self, frequency: Float) {
-        self.frequency = frequency as f32;
+    fn set_phase_offset(&mut self, offset: Float) {
+        self.phases
+            .iter_mut()
+            .enumerate()
+            .for_each(|(i, phase)| phase.offset = offset * (i + 1) as Float);
+    }
+
+    fn set_harmonics(&mut self, harmonics: Harmonics) {
+        self.harmonics = harmonics;
     }
 
     fn enable_overtones(&mut self, enabled: bool) {
@@ -107,4 +79,19 @@ impl Osc for Oscillator {
     fn overtones(&mut self) -> bool {
         self.overtones
     }
+
+    fn set_phase(&mut self, phase: Float) {
+        self.phases
+            .iter_mut()
+            .enumerate()
+            .for_each(|(i, p)| p.set(phase * (i + 1) as Float));
+    }
+
+    fn reverse_phase(&mut self) {
+        self.phases.iter_mut().for_each(Phase::reverse);
+    }
+
+    fn cycle_started(&self) -> bool {
+        self.phases[0].wrapped
+    }
 }
@@ -0,0 +1,118 @@
+use super::{DEFAULT_HARMONICS, HARMONICS_SIZE};
+use crate::Float;
+use anyhow::{Result, bail, ensure};
+
+/// the harmonic each organ drawbar plays (16', 5 1/3', 8', 4', 2 2/3', 2', 1 3/5', 1 1/3', 1').
+/// the 16' bar is the fundamental so registrations sound an octave below the 8' bar.
+pub const DRAWBAR_HARMONICS: [usize; 9] = [1, 3, 2, 4, 6, 8, 10, 12, 16];
+
+/// the names of the built in spectrum presets.
+pub const PRESETS: [&str; 6] = [
+    "default",
+    "saw",
+    "square",
+    "triangle",
+    "organ",
+    "full organ",
+];
+
+/// the amplitude and phase of every harmonic an oscillator plays in overtone mode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spectrum {
+    pub amplitudes: [Float; HARMONICS_SIZE],
+    /// phase of each harmonic in cycles (0.0 to 1.0).
+    pub phases: [Float; HARMONICS_SIZE],
+}
+
+impl Default for Spectrum {
+    fn default() -> Self {
+        Self {
+            amplitudes: DEFAULT_HARMONICS,
+            phases: [0.0; HARMONICS_SIZE],
+        }
+    }
+}
+
+impl Spectrum {
+    /// makes a spectrum from up to `HARMONICS_SIZE` amplitudes and phases, missing harmonics are
+    /// silent and missing phases are 0.
+    pub fn new(amplitudes: &[Float], phases: &[Float]) -> Self {
+        let mut spectrum = Self {
+            amplitudes: [0.0; HARMONICS_SIZE],
+            phases: [0.0; HARMONICS_SIZE],
+        };
+
+        spectrum
+            .amplitudes
+            .iter_mut()
+            .zip(amplitudes)
+            .for_each(|(a, amp)| *a = amp.clamp(0.0, 1.0));
+        spectrum
+            .phases
+            .iter_mut()
+            .zip(phases)
+            .for_each(|(p, phase)| *p = phase.rem_euclid(1.0));
+
+        spectrum
+    }
+
+    /// makes a spectrum from an organ drawbar registration, eg. "888000000". each bar is 0 to 8,
+    /// and each step is 3 dB.
+    pub fn drawbars(registration: &str) -> Result<Self> {
+        let registration = registration.trim();
+        ensure!(
+            registration.len() <= DRAWBAR_HARMONICS.len(),
+            "a drawbar registration has at most {} bars",
+            DRAWBAR_HARMONICS.len()
+        );
+
+        let mut amplitudes = [0.0; HARMONICS_SIZE];
+
+        for (bar, harmonic) in registration.chars().zip(DRAWBAR_HARMONICS) {
+            let Some(level) = bar.to_digit(10).filter(|level| *level <= 8) else {
+                bail!("{bar} is not a drawbar level (0 to 8)");
+            };
+
+            if level > 0 {
+                amplitudes[harmonic - 1] = (10.0 as Float).powf(-3.0 * (8 - level) as Float / 20.0);
+            }
+        }
+
+        Ok(Self::new(&amplitudes, &[]))
+    }
+
+    /// returns one of the `PRESETS`.
+    pub fn preset(name: &str) -> Option<Self> {
+        Some(match name.trim().to_lowercase().as_str() {
+            "default" => Self::default(),
+            "saw" => Self::new(&harmonics(|n| 1.0 / n as Float), &[]),
+            "square" => Self::new(&harmonics(|n| odd(n) / n as Float), &[]),
+            // odd harmonics falling at 12 dB per octave, every other one inverted.
+            "triangle" => Self::new(
+                &harmonics(|n| odd(n) / (n * n) as Float),
+                &harmonics(|n| if n % 4 == 3 { 0.5 } else { 0.0 }),
+            ),
+            "organ" => Self::drawbars("888000000").ok()?,
+            "full organ" => Self::drawbars("888888888").ok()?,
+            _ => return None,
+        })
+    }
+
+    /// iterates over the (amplitude, phase) of every harmonic.
+    pub fn iter(&self) -> impl Iterator<Item = (Float, Float)> + '_ {
+        self.amplitudes
+            .iter()
+            .copied()
+            .zip(self.phases.iter().copied())
+    }
+}
+
+/// builds a table from a function of the harmonic number (starting at 1).
+fn harmonics(f: impl Fn(usize) -> Float) -> [Float; HARMONICS_SIZE] {
+    std::array::from_fn(|i| f(i + 1))
+}
+
+/// 1.0 for odd harmonics, 0.0 for even ones.
+fn odd(n: usize) -> Float {
+    (n % 2) as Float
+}
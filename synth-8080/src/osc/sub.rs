@@ -0,0 +1,66 @@
+use super::blep::{self, Phase};
+use crate::Float;
+pub use lib::SubWaveform;
+use std::f32::consts::TAU;
+
+/// the most octaves below the main oscillator the sub oscillator can play.
+pub const MAX_SUB_OCTAVES: u8 = 2;
+
+/// a single, cheap oscillator (no overtone stack) that plays one or two octaves below the main one.
+#[derive(Debug, Clone)]
+pub struct SubOscillator {
+    phase: Phase,
+    /// the frequency of the main oscillator.
+    frequency: Float,
+    pub waveform: SubWaveform,
+    /// how many octaves below the main oscillator to play.
+    pub octaves: u8,
+    /// 0.0 turns the sub oscillator off.
+    pub level: Float,
+    /// frequency multiplier from frequency modulation, applied when the phase steps.
+    pub fm_ratio: Float,
+}
+
+impl Default for SubOscillator {
+    fn default() -> Self {
+        Self {
+            phase: Phase::default(),
+            frequency: 0.0,
+            waveform: SubWaveform::default(),
+            octaves: 1,
+            level: 0.0,
+            fm_ratio: 1.0,
+        }
+    }
+}
+
+impl SubOscillator {
+    /// sets the frequency of the main oscillator.
+    pub fn set_frequency(&mut self, frequency: Float) {
+        self.frequency = frequency;
+        self.phase
+            .set_frequency(frequency / (1 << self.octaves) as Float);
+    }
+
+    /// sets how many octaves (1 or 2) below the main oscillator to play.
+    pub fn set_octaves(&mut self, octaves: u8) {
+        self.octaves = octaves.clamp(1, MAX_SUB_OCTAVES);
+        self.set_frequency(self.frequency);
+    }
+
+    pub fn get_sample(&mut self) -> Float {
+        if self.level == 0.0 {
+            return 0.0;
+        }
+
+        let dt = self.phase.inc * self.fm_ratio;
+        let t = self.phase.step_by(self.fm_ratio);
+
+        let sample = match self.waveform {
+            SubWaveform::Square => blep::pulse(t, dt, 0.5),
+            SubWaveform::Sine => (t * TAU).sin(),
+        };
+
+        sample * self.level
+    }
+}
@@ -0,0 +1,401 @@
+use super::{NYQUIST, Osc, blep::Phase};
+use crate::Float;
+use anyhow::{Result, bail, ensure};
+use std::{
+    f64::consts::PI,
+    fs,
+    path::Path,
+    sync::{Arc, OnceLock},
+};
+use tracing::*;
+
+/// the number of samples in every frame of a wavetable, frames are resampled to this when loaded.
+pub const TABLE_SIZE: usize = 2048;
+/// the number of band limited copies of each frame. level `n` holds the harmonics up to
+/// `TABLE_SIZE / 2 >> n`.
+pub const MIP_LEVELS: usize = 11;
+
+/// a set of single cycle frames that can be morphed between. every frame is stored as a set of band
+/// limited "mipmaps" so high notes don't alias.
+#[derive(Debug, Clone)]
+pub struct Wavetable {
+    /// `frames[frame][mip level][sample]`
+    frames: Vec<Vec<Vec<Float>>>,
+}
+
+impl Wavetable {
+    /// makes a wavetable from frames of any length.
+    pub fn from_frames(frames: Vec<Vec<Float>>) -> Self {
+        let frames: Vec<Vec<f64>> = frames.iter().map(|frame| resample(frame)).collect();
+        let mips: Vec<Vec<Vec<f64>>> = frames.into_iter().map(mipmap).collect();
+
+        // normalize the whole table by the same amount so morphing doesn't jump in volume.
+        let peak = mips
+            .iter()
+            .flat_map(|levels| levels[0].iter())
+            .fold(0.0_f64, |peak, sample| peak.max(sample.abs()));
+        let gain = if peak > 0.0 { 1.0 / peak } else { 1.0 };
+
+        Self {
+            frames: mips
+                .into_iter()
+                .map(|levels| {
+                    levels
+                        .into_iter()
+                        .map(|table| table.iter().map(|s| (s * gain) as Float).collect())
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    /// a single frame sine wave.
+    pub fn sine() -> Self {
+        Self::from_frames(vec![
+            (0..TABLE_SIZE)
+                .map(|n| (2.0 * PI * n as f64 / TABLE_SIZE as f64).sin() as Float)
+                .collect(),
+        ])
+    }
+
+    /// loads a single cycle WAV file or a multi frame (Serum style) wavetable.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let (samples, frame_size) = read_wav(&fs::read(path)?)?;
+
+        let frame_size = match frame_size {
+            Some(size) => size,
+            None if samples.len() > TABLE_SIZE && samples.len() % TABLE_SIZE == 0 => TABLE_SIZE,
+            None => samples.len(),
+        };
+        ensure!(frame_size > 0, "{} has no samples", path.display());
+
+        let frames: Vec<Vec<Float>> = samples
+            .chunks_exact(frame_size)
+            .map(|frame| frame.to_vec())
+            .collect();
+        ensure!(
+            !frames.is_empty(),
+            "{} is shorter than one frame",
+            path.display()
+        );
+        info!(
+            "loaded {} wavetable frames from {}",
+            frames.len(),
+            path.display()
+        );
+
+        Ok(Self::from_frames(frames))
+    }
+
+    /// the number of frames in the table.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// returns the sample at `phase` (0.0 to 1.0) of the frame at `position` (0.0 to 1.0, morphing
+    /// between frames), using the mip level that doesn't alias at `frequency`.
+    pub fn sample(&self, phase: Float, position: Float, frequency: Float) -> Float {
+        let frequency = frequency.abs();
+
+        if frequency >= NYQUIST || self.frames.is_empty() {
+            return 0.0;
+        }
+
+        let max_harmonic = if frequency > 0.0 {
+            (NYQUIST / frequency) as usize
+        } else {
+            TABLE_SIZE / 2
+        };
+        let mut level = 0;
+
+        while level < MIP_LEVELS - 1 && (TABLE_SIZE / 2 >> level) > max_harmonic {
+            level += 1;
+        }
+
+        let position = position.clamp(0.0, 1.0) * (self.frames.len() - 1) as Float;
+        let frame = position as usize;
+        let next_frame = (frame + 1).min(self.frames.len() - 1);
+        let mix = position - frame as Float;
+
+        let a = lerp(&self.frames[frame][level], phase);
+        let b = lerp(&self.frames[next_frame][level], phase);
+
+        a + (b - a) * mix
+    }
+}
+
+/// the wavetable oscillators start with, a sine wave.
+pub fn default_table() -> Arc<Wavetable> {
+    static TABLE: OnceLock<Arc<Wavetable>> = OnceLock::new();
+
+    TABLE.get_or_init(|| Arc::new(Wavetable::sine())).clone()
+}
+
+/// reads `table` at `phase` (0.0 to 1.0) with linear interpolation.
+fn lerp(table: &[Float], phase: Float) -> Float {
+    let index = phase.rem_euclid(1.0) * table.len() as Float;
+    let i = (index as usize).min(table.len() - 1);
+    let next = (i + 1) % table.len();
+    let weight = index - i as Float;
+
+    table[i] * (1.0 - weight) + table[next] * weight
+}
+
+/// resamples a single cycle to `TABLE_SIZE` samples.
+fn resample(frame: &[Float]) -> Vec<f64> {
+    (0..TABLE_SIZE)
+        .map(|n| lerp(frame, n as Float / TABLE_SIZE as Float) as f64)
+        .collect()
+}
+
+/// makes the band limited copies of a frame by removing harmonics in the frequency domain.
+fn mipmap(frame: Vec<f64>) -> Vec<Vec<f64>> {
+    let mut spectrum: Vec<(f64, f64)> = frame.into_iter().map(|s| (s, 0.0)).collect();
+    fft(&mut spectrum, false);
+    // DC isn't heard and would just eat headroom.
+    spectrum[0] = (0.0, 0.0);
+
+    (0..MIP_LEVELS)
+        .map(|level| {
+            let max_harmonic = TABLE_SIZE / 2 >> level;
+            let mut bins: Vec<(f64, f64)> = spectrum
+                .iter()
+                .enumerate()
+                .map(|(k, bin)| {
+                    let harmonic = k.min(TABLE_SIZE - k);
+
+                    if harmonic <= max_harmonic {
+                        *bin
+                    } else {
+                        (0.0, 0.0)
+                    }
+                })
+                .collect();
+            fft(&mut bins, true);
+
+            bins.into_iter().map(|(re, _im)| re).collect()
+        })
+        .collect()
+}
+
+/// in place radix-2 FFT, `data.len()` must be a power of two. the inverse is scaled by 1/n.
+fn fft(data: &mut [(f64, f64)], inverse: bool) {
+    let n = data.len();
+    let mut j = 0;
+
+    for i in 1..n {
+        let mut bit = n >> 1;
+
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+
+        j |= bit;
+
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+
+    while len <= n {
+        let angle = if inverse { 2.0 } else { -2.0 } * PI / len as f64;
+        let (w_re, w_im) = (angle.cos(), angle.sin());
+
+        for start in (0..n).step_by(len) {
+            let (mut re, mut im) = (1.0, 0.0);
+
+            for k in 0..len / 2 {
+                let (a_re, a_im) = data[start + k];
+                let (b_re, b_im) = data[start + k + len / 2];
+                let (t_re, t_im) = (b_re * re - b_im * im, b_re * im + b_im * re);
+
+                data[start + k] = (a_re + t_re, a_im + t_im);
+                data[start + k + len / 2] = (a_re - t_re, a_im - t_im);
+                (re, im) = (re * w_re - im * w_im, re * w_im + im * w_re);
+            }
+        }
+
+        len <<= 1;
+    }
+
+    if inverse {
+        data.iter_mut()
+            .for_each(|(re, im)| (*re, *im) = (*re / n as f64, *im / n as f64));
+    }
+}
+
+/// reads the first channel of a WAV file. also returns the frame size if the file has a Serum style
+/// "clm " chunk.
+fn read_wav(bytes: &[u8]) -> Result<(Vec<Float>, Option<usize>)> {
+    ensure!(
+        bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WAVE",
+        "not a WAV file"
+    );
+
+    let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+    let u32_at =
+        |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+
+    // (format, channels, bits per sample)
+    let mut format = None;
+    let mut data = None;
+    let mut frame_size = None;
+    let mut i = 12;
+
+    while i + 8 <= bytes.len() {
+        let id = &bytes[i..i + 4];
+        let size = u32_at(i + 4) as usize;
+        let body = &bytes[i + 8..(i + 8 + size).min(bytes.len())];
+
+        match id {
+            b"fmt " if body.len() >= 16 => {
+                let tag = u16_at(i + 8);
+                // WAVE_FORMAT_EXTENSIBLE keeps the real format in the sub format GUID.
+                let tag = if tag == 0xFFFE && body.len() >= 26 {
+                    u16_at(i + 8 + 24)
+                } else {
+                    tag
+                };
+
+                format = Some((tag, u16_at(i + 10) as usize, u16_at(i + 22) as usize));
+            }
+            b"data" => data = Some(body),
+            b"clm " => {
+                frame_size = String::from_utf8_lossy(body)
+                    .strip_prefix("<!>")
+                    .and_then(|text| {
+                        text.split(|c: char| !c.is_ascii_digit())
+                            .next()
+                            .and_then(|size| size.parse().ok())
+                    })
+            }
+            _ => {}
+        }
+
+        // chunks are padded to an even number of bytes.
+        i += 8 + size + size % 2;
+    }
+
+    let Some((tag, channels, bits)) = format else {
+        bail!("WAV file has no fmt chunk");
+    };
+    let Some(data) = data else {
+        bail!("WAV file has no data chunk");
+    };
+
+    let width = bits / 8;
+    ensure!(width > 0 && channels > 0, "invalid WAV format");
+
+    let samples = data
+        .chunks_exact(width * channels)
+        .map(|frame| {
+            let s = &frame[..width];
+
+            Ok(match (tag, bits) {
+                (1, 8) => (s[0] as Float - 128.0) / 128.0,
+                (1, 16) => i16::from_le_bytes([s[0], s[1]]) as Float / 32_768.0,
+                (1, 24) => (i32::from_le_bytes([0, s[0], s[1], s[2]]) >> 8) as Float / 8_388_608.0,
+                (1, 32) => i32::from_le_bytes([s[0], s[1], s[2], s[3]]) as Float / 2_147_483_648.0,
+                (3, 32) => f32::from_le_bytes([s[0], s[1], s[2], s[3]]) as Float,
+                (3, 64) => {
+                    f64::from_le_bytes([s[0], s[1], s[2], s[3], s[4], s[5], s[6], s[7]]) as Float
+                }
+                _ => bail!("unsupported WAV format {tag} with {bits} bit samples"),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok((samples, frame_size))
+}
+
+/// an oscillator that plays a wavetable.
+#[derive(Debug, Clone)]
+pub struct Oscillator {
+    table: Arc<Wavetable>,
+    phase: Phase,
+    frequency: Float,
+    /// where in the table to play from, 0.0 is the first frame and 1.0 the last.
+    position: Float,
+    volume: Float,
+    overtones: bool,
+    /// frequency multiplier from frequency modulation, applied when the phase steps.
+    fm_ratio: Float,
+}
+
+impl Oscillator {
+    pub fn new(table: Arc<Wavetable>) -> Self {
+        Self {
+            table,
+            phase: Phase::default(),
+            frequency: 0.0,
+            position: 0.0,
+            volume: 1.0,
+            overtones: false,
+            fm_ratio: 1.0,
+        }
+    }
+}
+
+impl Osc for Oscillator {
+    fn puretone_sample(&mut self) -> Float {
+        self.table.sample(
+            self.phase.step_by(self.fm_ratio),
+            self.position,
+            self.frequency * self.fm_ratio,
+        ) * self.volume
+    }
+
+    fn overtones_sample(&mut self) -> Float {
+        // the harmonics are in the table already.
+        self.puretone_sample()
+    }
+
+    fn set_frequency(&mut self, frequency: Float) {
+        self.frequency = frequency;
+        self.phase.set_frequency(frequency);
+    }
+
+    fn enable_overtones(&mut self, enabled: bool) {
+        self.overtones = enabled;
+    }
+
+    fn overtones(&mut self) -> bool {
+        self.overtones
+    }
+
+    fn set_fm_ratio(&mut self, ratio: Float) {
+        self.fm_ratio = ratio;
+    }
+
+    fn set_phase_offset(&mut self, offset: Float) {
+        self.phase.offset = offset;
+    }
+
+    fn set_phase(&mut self, phase: Float) {
+        self.phase.set(phase);
+    }
+
+    fn reverse_phase(&mut self) {
+        self.phase.reverse();
+    }
+
+    fn cycle_started(&self) -> bool {
+        self.phase.wrapped
+    }
+
+    fn set_position(&mut self, position: Float) {
+        self.position = position;
+    }
+
+    fn set_wavetable(&mut self, table: Arc<Wavetable>) {
+        self.table = table;
+    }
+}
@@ -12,18 +12,29 @@ use tracing::*;
 // use tokio::spawn;
 
 // TODO: Add a volume input to this
-pub const N_INPUTS: u8 = 1;
+pub const N_INPUTS: u8 = 3;
 pub const N_OUTPUTS: u8 = 0;
+/// played on both channels.
+pub const MONO_IN: u8 = 0;
+pub const LEFT_IN: u8 = 1;
+pub const RIGHT_IN: u8 = 2;
 
 #[derive(Clone)]
 pub struct Audio {
     ext_sync: Sender<()>,
-    int_sync: Receiver<Float>,
+    /// (left, right) frames
+    int_sync: Receiver<(Float, Float)>,
+    /// the right sample of the current frame, samples are interleaved left then right.
+    right: Option<Float>,
 }
 
 impl Audio {
-    pub fn new(ext_sync: Sender<()>, int_sync: Receiver<Float>) -> Self {
-        Self { ext_sync, int_sync }
+    pub fn new(ext_sync: Sender<()>, int_sync: Receiver<(Float, Float)>) -> Self {
+        Self {
+            ext_sync,
+            int_sync,
+            right: None,
+        }
     }
 }
 
@@ -31,12 +42,17 @@ impl Iterator for Audio {
     type Item = f32;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if let Some(right) = self.right.take() {
+            return Some(right as f32);
+        }
+
         // self.ext_sync.send(()).unwrap();
-        let sample = self.int_sync.try_recv().unwrap_or(0.0);
+        let (left, right) = self.int_sync.try_recv().unwrap_or((0.0, 0.0));
+        self.right = Some(right);
         // #[cfg(not(feature = "hardware"))]
         self.ext_sync.send(()).unwrap();
         // info!("sample => {sample}");
-        Some(sample as f32)
+        Some(left as f32)
     }
 }
 
@@ -46,7 +62,7 @@ impl Source for Audio {
     }
 
     fn channels(&self) -> u16 {
-        1
+        2
     }
 
     fn sample_rate(&self) -> u32 {
@@ -151,10 +167,13 @@ impl HWAudio {
 
 pub struct Output {
     /// used for internal synchronization with the audio buffer sent to rodio
-    int_sync: Sender<Float>,
+    int_sync: Sender<(Float, Float)>,
     hw_send: Sender<Float>,
     /// the current sample
     sample: Float,
+    /// the left and right samples of the frame being built, see `send_frame`.
+    left: Float,
+    right: Float,
     /// the rodio output stream, it isn't used but must never be dropped else audio output will cease
     pub stream: OutputStream,
     pub volume: Float,
@@ -214,6 +233,8 @@ impl Output {
                 // ext_sync,
                 int_sync,
                 sample,
+                left: 0.0,
+                right: 0.0,
                 stream,
                 volume: 1.0,
                 // hw_audio_thread,
@@ -232,16 +253,14 @@ impl Output {
     pub fn set_volume(&mut self, volume: Float) {
         self.volume = volume;
     }
-}
-
-impl Module for Output {
-    fn get_samples(&mut self) -> Vec<(u8, Float)> {
-        vec![(0, self.sample)]
-    }
 
-    fn recv_samples(&mut self, _input_n: u8, samples: &[Float]) {
-        let sample: Float = samples.iter().sum();
-        self.sample = (sample * self.volume).tanh();
+    /// sends the frame built from the inputs received since the last call to the audio device.
+    pub fn send_frame(&mut self) {
+        let left = (self.left * self.volume).tanh();
+        let right = (self.right * self.volume).tanh();
+        self.left = 0.0;
+        self.right = 0.0;
+        self.sample = (left + right) * 0.5;
         // warn!("sample -> {sample}");
 
         // if let Err(e) = self.int_sync.send(self.sample) {
@@ -249,7 +268,7 @@ impl Module for Output {
         // };
 
         #[cfg(not(feature = "hardware"))]
-        if let Err(e) = self.int_sync.send(self.sample) {
+        if let Err(e) = self.int_sync.send((left, right)) {
             error!("could not send sample to Audio struct. got error: {e}");
         };
 
@@ -258,9 +277,30 @@ impl Module for Output {
             error!("could not send sample to hardware audio controller struct. got error: {e}");
         }
     }
+}
+
+impl Module for Output {
+    fn get_samples(&mut self) -> Vec<(u8, Float)> {
+        vec![(0, self.sample)]
+    }
+
+    fn recv_samples(&mut self, input_n: u8, samples: &[Float]) {
+        let sample: Float = samples.iter().sum();
+
+        if input_n == MONO_IN {
+            self.left += sample;
+            self.right += sample;
+        } else if input_n == LEFT_IN {
+            self.left += sample;
+        } else if input_n == RIGHT_IN {
+            self.right += sample;
+        } else {
+            error!("invalid input: {input_n} for the output module");
+        }
+    }
 
     fn get_input_names() -> impl Iterator<Item = impl std::fmt::Display> {
-        ["Audio In"].iter()
+        ["Audio In", "Left In", "Right In"].iter()
     }
 
     fn get_output_names() -> impl Iterator<Item = impl std::fmt::Display> {
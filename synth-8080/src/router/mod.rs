@@ -1,4 +1,4 @@
-use crate::{common::Module, Float};
+use crate::{Float, common::Module};
 use lib::ModuleType;
 use tracing::*;
 
@@ -19,6 +19,11 @@ pub struct Modules {
     pub chorus: Vec<crate::chorus::Chorus>,
     pub over_drive: Vec<crate::overdrive::OverDrive>,
     pub mco: Vec<crate::midi_osc::MidiOsc>,
+    pub noise: Vec<crate::noise::NoiseModule>,
+    pub env_gen: Vec<crate::env_gen::EnvelopeGenerator>,
+    pub filter_module: Vec<crate::filter::FilterModule>,
+    pub formant: Vec<crate::formant::FormantFilter>,
+    pub follower: Vec<crate::follower::EnvelopeFollower>,
     // pub audio_in: Vec<(Vec<Input>, Vec<Output>)>,
     /// allows for easier indexing into this struct. the index of the items in this Vec correspond
     /// to the modules ID
@@ -45,8 +50,15 @@ impl Modules {
             ModuleType::OverDrive => self.over_drive[*i].get_samples(),
             ModuleType::Reverb => self.reverb[*i].get_samples(),
             ModuleType::MCO => self.mco[*i].get_samples(),
+            ModuleType::Noise => self.noise[*i].get_samples(),
+            ModuleType::Envelope => self.env_gen[*i].get_samples(),
+            ModuleType::Filter => self.filter_module[*i].get_samples(),
+            ModuleType::Formant => self.formant[*i].get_samples(),
+            ModuleType::Follower => self.follower[*i].get_samples(),
             _ => {
-                error!("{mod_type:?} is not yet in Modules.get_output(...)'s match statement. pls fix that");
+                error!(
+                    "{mod_type:?} is not yet in Modules.get_output(...)'s match statement. pls fix that"
+                );
                 return None;
             }
         })
@@ -71,8 +83,15 @@ impl Modules {
             ModuleType::OverDrive => self.over_drive[i].recv_samples(input as u8, samples),
             ModuleType::Reverb => self.reverb[i].recv_samples(input as u8, samples),
             ModuleType::MCO => self.mco[i].recv_samples(input as u8, samples),
+            ModuleType::Noise => self.noise[i].recv_samples(input as u8, samples),
+            ModuleType::Envelope => self.env_gen[i].recv_samples(input as u8, samples),
+            ModuleType::Filter => self.filter_module[i].recv_samples(input as u8, samples),
+            ModuleType::Formant => self.formant[i].recv_samples(input as u8, samples),
+            ModuleType::Follower => self.follower[i].recv_samples(input as u8, samples),
             _ => {
-                error!("{mod_type:?} is not yet in Modules.get_output(...)'s match statement. pls fix that");
+                error!(
+                    "{mod_type:?} is not yet in Modules.get_output(...)'s match statement. pls fix that"
+                );
                 return;
             }
         }
@@ -128,6 +147,26 @@ impl From<&[ModuleType]> for Modules {
                     s.mco.push(crate::midi_osc::MidiOsc::default());
                     s.indices.push((*mod_type, s.mco.len() - 1));
                 }
+                ModuleType::Noise => {
+                    s.noise.push(crate::noise::NoiseModule::new());
+                    s.indices.push((*mod_type, s.noise.len() - 1));
+                }
+                ModuleType::Envelope => {
+                    s.env_gen.push(crate::env_gen::EnvelopeGenerator::new());
+                    s.indices.push((*mod_type, s.env_gen.len() - 1));
+                }
+                ModuleType::Filter => {
+                    s.filter_module.push(crate::filter::FilterModule::new());
+                    s.indices.push((*mod_type, s.filter_module.len() - 1));
+                }
+                ModuleType::Formant => {
+                    s.formant.push(crate::formant::FormantFilter::new());
+                    s.indices.push((*mod_type, s.formant.len() - 1));
+                }
+                ModuleType::Follower => {
+                    s.follower.push(crate::follower::EnvelopeFollower::new());
+                    s.indices.push((*mod_type, s.follower.len() - 1));
+                }
                 _ => {
                     error!(
                         "{mod_type:?} is not yet in Modules.from(...)'s match statement. pls fix that"
@@ -1,17 +1,33 @@
 use crate::{
     Float,
     common::{Module, bend_range, notes::Note},
-    osc::{OscType, Oscillator},
+    osc::{Harmonics, OscType, Oscillator, SyncMode, sub::SubWaveform},
 };
 use lib::midi_to_freq;
-use std::sync::Arc;
+use std::{
+    f32::consts::{FRAC_1_SQRT_2, TAU},
+    sync::Arc,
+};
 use tracing::*;
 
-pub const N_INPUTS: u8 = 3;
-pub const N_OUTPUTS: u8 = 1;
+pub const N_INPUTS: u8 = 9;
+pub const N_OUTPUTS: u8 = 4;
 pub const VOLUME_INPUT: u8 = 0;
 pub const PITCH_INPUT: u8 = 1;
 pub const PITCH_BEND_INPUT: u8 = 2;
+pub const POSITION_INPUT: u8 = 3;
+pub const PWM_INPUT: u8 = 4;
+pub const FM_INPUT: u8 = 5;
+pub const FM_INDEX_INPUT: u8 = 6;
+pub const PM_INPUT: u8 = 7;
+pub const SYNC_INPUT: u8 = 8;
+pub const AUDIO_OUT: u8 = 0;
+/// 1.0 on the sample each cycle starts, for syncing other oscillators.
+pub const SYNC_OUT: u8 = 1;
+pub const LEFT_OUT: u8 = 2;
+pub const RIGHT_OUT: u8 = 3;
+/// the FM index when the FM index input is at 1.0.
+pub const MAX_FM_INDEX: Float = 10.0;
 
 pub struct Vco {
     /// stores the current oscillator type (probably not necessary)
@@ -20,11 +36,30 @@ pub struct Vco {
     pub osc: Oscillator,
     /// where the data from the volume input is stored
     pub volume_in: Float,
+    /// per note amplitude from channel pressure (MPE).
+    pub pressure: Float,
     /// the note to be played
     pub pitch_in: Float,
     /// whether the oscillator should produce over tones.
     pub overtones: bool,
     pub note: Note,
+    /// wavetable position set from the controls
+    pub position: Float,
+    /// pulse width set from the controls, the PWM input is added on top of this.
+    pub pulse_width: Float,
+    /// FM index set from the controls, the FM index input is added on top of this.
+    pub fm_index: Float,
+    /// FM index including the FM index input.
+    fm_index_in: Float,
+    /// the FM and PM inputs, these are audio rate so they are cleared after every sample.
+    fm_in: Float,
+    pm_in: Float,
+    /// lets FM push the frequency below zero, running the oscillator backwards.
+    pub through_zero: bool,
+    /// what happens when the sync input crosses zero upward.
+    pub sync_mode: SyncMode,
+    /// the last sample from the sync input, to find zero crossings.
+    sync_in: Float,
     /// how much to bend the pitch when pitch bends happen
     pub bend_amt: Arc<Float>,
     /// the id of this module, must correspond to its index in the routing table
@@ -50,9 +85,19 @@ impl Vco {
             osc_type,
             osc,
             volume_in,
+            pressure: 1.0,
             pitch_in,
             overtones,
             note,
+            position: 0.0,
+            pulse_width: 0.5,
+            fm_index: 1.0,
+            fm_index_in: 1.0,
+            fm_in: 0.0,
+            pm_in: 0.0,
+            through_zero: false,
+            sync_mode: SyncMode::default(),
+            sync_in: 0.0,
             bend_amt,
             id,
         }
@@ -78,6 +123,64 @@ impl Vco {
         // info!("set note to {note}")
     }
 
+    /// sets the wavetable position (0.0 to 1.0), the position input is added on top of this.
+    pub fn set_position(&mut self, position: Float) {
+        self.position = position;
+        self.osc.set_position(position);
+    }
+
+    /// sets the pulse width of the square wave (0.0 to 1.0), 0.5 is a square.
+    pub fn set_pulse_width(&mut self, width: Float) {
+        self.pulse_width = width;
+        self.osc.set_pulse_width(width);
+    }
+
+    /// sets the FM index (0.0 to `MAX_FM_INDEX`), how strongly the FM and PM inputs modulate.
+    pub fn set_fm_index(&mut self, index: Float) {
+        self.fm_index = index.max(0.0);
+        self.fm_index_in = self.fm_index;
+    }
+
+    pub fn set_through_zero(&mut self, on: bool) {
+        self.through_zero = on;
+    }
+
+    /// sets the number of unison voices, see `Oscillator::set_unison`.
+    pub fn set_unison(&mut self, n: usize) {
+        self.osc.set_unison(n);
+    }
+
+    pub fn set_detune(&mut self, detune: Float) {
+        self.osc.set_detune(detune);
+    }
+
+    pub fn set_spread(&mut self, spread: Float) {
+        self.osc.set_spread(spread);
+    }
+
+    /// sets the harmonics played in overtone mode.
+    pub fn set_harmonics(&mut self, harmonics: Harmonics) {
+        self.osc.set_harmonics(harmonics);
+    }
+
+    /// sets the level of the sub oscillator, 0.0 turns it off.
+    pub fn set_sub_level(&mut self, level: Float) {
+        self.osc.set_sub_level(level);
+    }
+
+    /// sets how many octaves (1 or 2) below the note the sub oscillator plays.
+    pub fn set_sub_octaves(&mut self, octaves: u8) {
+        self.osc.set_sub_octaves(octaves);
+    }
+
+    pub fn set_sub_waveform(&mut self, waveform: SubWaveform) {
+        self.osc.set_sub_waveform(waveform);
+    }
+
+    pub fn set_sync_mode(&mut self, mode: SyncMode) {
+        self.sync_mode = mode;
+    }
+
     // pub fn set_freq(&mut self, note: Float) {
     //     // self.note = note;
     //     self.osc.set_frequency(note);
@@ -88,9 +191,27 @@ impl Vco {
 
 impl Module for Vco {
     fn get_samples(&mut self) -> Vec<(u8, Float)> {
-        let sample = self.osc.get_sample() * self.volume_in;
+        // PM input is in radians, like a DX7's.
+        self.osc.modulate(
+            self.fm_in * self.fm_index_in,
+            self.pm_in * self.fm_index_in / TAU,
+            self.through_zero,
+        );
+        self.fm_in = 0.0;
+        self.pm_in = 0.0;
+
+        let gain = self.volume_in * self.pressure;
+        let (left, right) = self.osc.get_stereo_sample();
+        let (left, right) = (left * gain, right * gain);
+        let sample = (left + right) * FRAC_1_SQRT_2;
+        let sync = if self.osc.cycle_started() { 1.0 } else { 0.0 };
         // info!("sample {sample}");
-        vec![(0, sample)]
+        vec![
+            (AUDIO_OUT, sample),
+            (SYNC_OUT, sync),
+            (LEFT_OUT, left),
+            (RIGHT_OUT, right),
+        ]
     }
 
     fn recv_samples(&mut self, input_n: u8, samples: &[Float]) {
@@ -100,16 +221,41 @@ impl Module for Vco {
             self.volume_in = (samples.iter().sum::<Float>().tanh() + 1.0) * 0.5;
         } else if input_n == PITCH_BEND_INPUT {
             self.osc.apply_bend(samples.iter().sum::<Float>().tanh());
+        } else if input_n == POSITION_INPUT {
+            self.osc
+                .set_position(self.position + samples.iter().sum::<Float>());
+        } else if input_n == PWM_INPUT {
+            // a full swing of the input sweeps the whole usable width.
+            self.osc
+                .set_pulse_width(self.pulse_width + samples.iter().sum::<Float>() * 0.5);
+        } else if input_n == FM_INPUT {
+            self.fm_in = samples.iter().sum::<Float>();
+        } else if input_n == FM_INDEX_INPUT {
+            self.fm_index_in =
+                (self.fm_index + samples.iter().sum::<Float>() * MAX_FM_INDEX).max(0.0);
+        } else if input_n == PM_INPUT {
+            self.pm_in = samples.iter().sum::<Float>();
+        } else if input_n == SYNC_INPUT {
+            let sync_in = samples.iter().sum::<Float>();
+
+            if self.sync_in <= 0.0 && sync_in > 0.0 {
+                self.osc.sync(self.sync_mode);
+            }
+
+            self.sync_in = sync_in;
         } else {
             error!("invalid input: {input_n} for VCO module");
         }
     }
 
     fn get_input_names() -> impl Iterator<Item = impl std::fmt::Display> {
-        ["Vol.", "Pitch", "Bend"].iter()
+        [
+            "Vol.", "Pitch", "Bend", "Position", "PWM", "FM", "FM Index", "PM", "Sync",
+        ]
+        .iter()
     }
 
     fn get_output_names() -> impl Iterator<Item = impl std::fmt::Display> {
-        ["Audio Out"].iter()
+        ["Audio Out", "Sync Out", "Left Out", "Right Out"].iter()
     }
 }